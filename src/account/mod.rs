@@ -0,0 +1,167 @@
+mod usage;
+
+use crate::{
+    account::usage::AccountUsage,
+    args,
+    find_blobs_by_tags::{output_blobs_by_tags, TagFilter},
+    paging::{print_marker, Paging},
+    rest::RestClient,
+    sas::{
+        account_sas, shared_key, AccountSasPermissions, AccountSasResourceTypes,
+        AccountSasServices, SasOptions,
+    },
+    utils::output,
+};
+use azure_storage_blobs::prelude::BlobServiceClient;
+use clap::Subcommand;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+#[derive(Subcommand)]
+pub enum AccountSubCommands {
+    /// Get information about the storage account
+    Info,
+    /// List the storage containers in the account
+    ///
+    /// The output of this command is serialized to JSON unless the `show_details` flag is set
+    ListContainers {
+        #[clap(long)]
+        prefix: Option<String>,
+        #[clap(long)]
+        include_metadata: bool,
+        #[clap(long)]
+        include_deleted: bool,
+        /// max results to return per page
+        #[clap(long, visible_alias = "page-size")]
+        max_results: Option<NonZeroU32>,
+        #[clap(long, conflicts_with = "envelope")]
+        show_details: bool,
+        #[clap(flatten)]
+        paging: Paging,
+    },
+    /// Generate an account SAS, signed with the account access key
+    GenerateSas {
+        #[clap(flatten)]
+        options: SasOptions,
+        /// Services the SAS can access, as any combination of `b` (blob), `q` (queue), `t` (table), and `f` (file)
+        #[clap(long)]
+        services: AccountSasServices,
+        /// Resource types the SAS can access, as any combination of `s` (service), `c` (container), and `o` (object)
+        #[clap(long)]
+        resource_types: AccountSasResourceTypes,
+        /// Encryption scope used for requests authorized by the SAS
+        #[clap(long)]
+        encryption_scope: Option<String>,
+        #[clap(flatten)]
+        permissions: AccountSasPermissions,
+    },
+    /// Find blobs across the account with tags matching a filter expression
+    ///
+    /// For example: `"project" = 'alpha' AND "priority" >= '2'`.  Use
+    /// `@container = 'name'` to limit the search to a single container.
+    FindBlobsByTags {
+        expression: TagFilter,
+        /// max results to return per request
+        #[clap(long)]
+        max_results: Option<NonZeroU32>,
+    },
+    /// Summarize the storage used by each container in the account
+    ///
+    /// The current version of each blob is counted by access tier, blob type, and
+    /// age since last modified, while snapshots, previous versions, and
+    /// soft-deleted blobs are counted separately.
+    Usage(AccountUsage),
+}
+
+#[derive(Serialize)]
+struct AccountSas {
+    token: String,
+    urls: BTreeMap<&'static str, String>,
+}
+
+pub async fn account_commands(
+    service_client: &BlobServiceClient,
+    rest_client: &RestClient,
+    subcommand: AccountSubCommands,
+) -> azure_core::Result<()> {
+    match subcommand {
+        AccountSubCommands::Info => {
+            let info = service_client.get_account_information().await?;
+            println!("{info:#?}");
+        }
+        AccountSubCommands::ListContainers {
+            prefix,
+            include_deleted,
+            include_metadata,
+            max_results,
+            show_details,
+            paging,
+        } => {
+            let mut builder = service_client
+                .list_containers()
+                .include_deleted(include_deleted)
+                .include_metadata(include_metadata)
+                .context(paging.context("marker"));
+            args!(builder, prefix, max_results);
+            if show_details {
+                let mut pages = paging.pages(builder.into_stream());
+                while let Some(page) = pages.next().await {
+                    for container in page?.containers {
+                        println!("{container:#?}");
+                    }
+                }
+                print_marker(pages.next_marker());
+            } else {
+                paging
+                    .output_stream(builder.into_stream(), |page| {
+                        Ok(page
+                            .containers
+                            .into_iter()
+                            .map(|container| container.name)
+                            .collect())
+                    })
+                    .await?;
+            }
+        }
+        AccountSubCommands::GenerateSas {
+            options,
+            services,
+            resource_types,
+            encryption_scope,
+            permissions,
+        } => {
+            let (account, key) = shared_key(rest_client, "account").await?;
+            let token = account_sas(
+                &account,
+                &key,
+                &services,
+                &resource_types,
+                &permissions,
+                &options.validity()?,
+                encryption_scope.as_deref(),
+            )?;
+
+            let mut urls = BTreeMap::new();
+            for (service, name) in [('b', "blob"), ('q', "queue"), ('t', "table"), ('f', "file")] {
+                if services.contains(service) {
+                    let mut url = rest_client.service_url(name)?;
+                    url.set_query(Some(&token));
+                    urls.insert(name, url.to_string());
+                }
+            }
+            output(&AccountSas { token, urls })?;
+        }
+        AccountSubCommands::FindBlobsByTags {
+            expression,
+            max_results,
+        } => {
+            output_blobs_by_tags(rest_client, service_client.url()?, &expression, max_results)
+                .await?;
+        }
+        AccountSubCommands::Usage(usage) => {
+            usage.execute(service_client).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use crate::utils::{format_bytes, output, serialized_name};
+use azure_core::error::{Error, ErrorKind, Result};
+use azure_storage_blobs::{
+    blob::Blob,
+    prelude::{BlobServiceClient, ContainerClient},
+};
+use clap::ValueEnum;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Write, fs, path::PathBuf};
+use time::OffsetDateTime;
+use tracing::error;
+
+/// Upper bounds, in days since the blob was last modified, of the age buckets
+const AGE_BUCKETS: [(i64, &str); 4] = [
+    (30, "0-30d"),
+    (90, "30-90d"),
+    (180, "90-180d"),
+    (365, "180-365d"),
+];
+const OLDEST_AGE_BUCKET: &str = "365d+";
+
+/// Container name used for the account totals in CSV reports, which cannot
+/// be the name of a container
+const TOTAL: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Usage of each container and of the account, as JSON
+    Json,
+    /// One row per container, followed by the account totals
+    Table,
+    /// One row per container, category and key, with `*` as the container
+    /// for the account totals
+    Csv,
+}
+
+/// Summarize the storage used by each container in the account
+#[derive(clap::Parser)]
+pub struct AccountUsage {
+    /// only include containers with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// Number of containers to list at a time
+    #[clap(long, default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    /// Format of the report
+    #[clap(long, value_enum, default_value_t = ReportFormat::Json)]
+    format: ReportFormat,
+    /// File to write the report to, rather than STDOUT
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct Usage {
+    blobs: u64,
+    bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.blobs += other.blobs;
+        self.bytes += other.bytes;
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ContainerUsage {
+    /// Current versions of the blobs
+    current: Usage,
+    by_tier: BTreeMap<String, Usage>,
+    by_blob_type: BTreeMap<String, Usage>,
+    by_age: BTreeMap<String, Usage>,
+    snapshots: Usage,
+    versions: Usage,
+    deleted: Usage,
+}
+
+impl ContainerUsage {
+    fn add_blob(&mut self, blob: &Blob, now: OffsetDateTime) {
+        let usage = Usage {
+            blobs: 1,
+            bytes: blob.properties.content_length,
+        };
+        if blob.deleted == Some(true) {
+            self.deleted.add(usage);
+        } else if blob.snapshot.is_some() {
+            self.snapshots.add(usage);
+        } else if blob.is_current_version == Some(false) {
+            self.versions.add(usage);
+        } else {
+            self.current.add(usage);
+            let tier = blob
+                .properties
+                .access_tier
+                .as_ref()
+                .map_or_else(|| "none".to_string(), serialized_name);
+            self.by_tier.entry(tier).or_default().add(usage);
+            self.by_blob_type
+                .entry(serialized_name(&blob.properties.blob_type))
+                .or_default()
+                .add(usage);
+            self.by_age
+                .entry(age_bucket(now - blob.properties.last_modified).to_string())
+                .or_default()
+                .add(usage);
+        }
+    }
+
+    fn add(&mut self, other: &ContainerUsage) {
+        self.current.add(other.current);
+        for (totals, usage) in [
+            (&mut self.by_tier, &other.by_tier),
+            (&mut self.by_blob_type, &other.by_blob_type),
+            (&mut self.by_age, &other.by_age),
+        ] {
+            for (key, usage) in usage {
+                totals.entry(key.clone()).or_default().add(*usage);
+            }
+        }
+        self.snapshots.add(other.snapshots);
+        self.versions.add(other.versions);
+        self.deleted.add(other.deleted);
+    }
+
+    /// Rows of the CSV report, as the category, key, and usage
+    fn rows(&self) -> Vec<(&'static str, &str, Usage)> {
+        let mut rows = vec![("current", "", self.current)];
+        for (category, usage) in [
+            ("tier", &self.by_tier),
+            ("blob_type", &self.by_blob_type),
+            ("age", &self.by_age),
+        ] {
+            rows.extend(
+                usage
+                    .iter()
+                    .map(|(key, usage)| (category, key.as_str(), *usage)),
+            );
+        }
+        rows.push(("snapshots", "", self.snapshots));
+        rows.push(("versions", "", self.versions));
+        rows.push(("deleted", "", self.deleted));
+        rows
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    total: ContainerUsage,
+    containers: BTreeMap<String, ContainerUsage>,
+}
+
+fn age_bucket(age: time::Duration) -> &'static str {
+    let days = age.whole_days();
+    AGE_BUCKETS
+        .iter()
+        .find(|(limit, _)| days < *limit)
+        .map_or(OLDEST_AGE_BUCKET, |(_, name)| name)
+}
+
+impl AccountUsage {
+    pub async fn execute(self, service_client: &BlobServiceClient) -> Result<()> {
+        let mut builder = service_client.list_containers();
+        if let Some(prefix) = &self.prefix {
+            builder = builder.prefix(prefix.clone());
+        }
+        let mut names = vec![];
+        let mut pages = builder.into_stream();
+        while let Some(response) = pages.next().await {
+            names.extend(
+                response?
+                    .containers
+                    .into_iter()
+                    .map(|container| container.name),
+            );
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let results = stream::iter(names)
+            .map(|name| async move {
+                let result = container_usage(&service_client.container_client(&name), now).await;
+                (name, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut report = Report {
+            total: ContainerUsage::default(),
+            containers: BTreeMap::new(),
+        };
+        let mut failed = 0;
+        for (name, result) in results {
+            match result {
+                Ok(usage) => {
+                    report.total.add(&usage);
+                    report.containers.insert(name, usage);
+                }
+                Err(err) => {
+                    error!("{name}: {err}");
+                    failed += 1;
+                }
+            }
+        }
+
+        match (self.format, &self.output) {
+            (ReportFormat::Json, None) => output(&report)?,
+            (ReportFormat::Json, Some(path)) => {
+                fs::write(path, serde_json::to_vec_pretty(&report)?)?;
+            }
+            (ReportFormat::Table, None) => print!("{}", table(&report)),
+            (ReportFormat::Table, Some(path)) => fs::write(path, table(&report))?,
+            (ReportFormat::Csv, None) => print!("{}", csv(&report)),
+            (ReportFormat::Csv, Some(path)) => fs::write(path, csv(&report))?,
+        }
+
+        if failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to list {failed} containers")
+            }));
+        }
+        Ok(())
+    }
+}
+
+async fn container_usage(
+    container_client: &ContainerClient,
+    now: OffsetDateTime,
+) -> Result<ContainerUsage> {
+    let mut usage = ContainerUsage::default();
+    let mut stream = container_client
+        .list_blobs()
+        .include_snapshots(true)
+        .include_versions(true)
+        .include_deleted(true)
+        .into_stream();
+    while let Some(response) = stream.next().await {
+        for blob in response?.blobs.blobs() {
+            usage.add_blob(blob, now);
+        }
+    }
+    Ok(usage)
+}
+
+fn csv(report: &Report) -> String {
+    let mut csv = "container,category,key,blobs,bytes\n".to_string();
+    let containers = report
+        .containers
+        .iter()
+        .map(|(name, usage)| (name.as_str(), usage))
+        .chain([(TOTAL, &report.total)]);
+    for (name, usage) in containers {
+        for (category, key, usage) in usage.rows() {
+            let _ = writeln!(
+                csv,
+                "{name},{category},{key},{},{}",
+                usage.blobs, usage.bytes
+            );
+        }
+    }
+    csv
+}
+
+/// The number and size of the current blobs in each container, with the size
+/// of their snapshots, previous versions, and soft-deleted blobs
+fn table(report: &Report) -> String {
+    let containers = report
+        .containers
+        .iter()
+        .map(|(name, usage)| (name.as_str(), usage))
+        .chain([("total", &report.total)]);
+    let name_width = containers
+        .clone()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default()
+        .max("CONTAINER".len());
+
+    let mut table = String::new();
+    let _ = writeln!(
+        table,
+        "{:<name_width$}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+        "CONTAINER", "BLOBS", "SIZE", "SNAPSHOTS", "VERSIONS", "DELETED"
+    );
+    for (name, usage) in containers {
+        let _ = writeln!(
+            table,
+            "{name:<name_width$}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+            usage.current.blobs,
+            format_bytes(usage.current.bytes),
+            format_bytes(usage.snapshots.bytes),
+            format_bytes(usage.versions.bytes),
+            format_bytes(usage.deleted.bytes)
+        );
+    }
+    table
+}
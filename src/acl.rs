@@ -0,0 +1,267 @@
+//! Stored access policies, which are the ACLs of containers, queues and tables.
+//!
+//! A SAS that refers to a stored access policy with `--identifier` can be
+//! revoked by removing or changing the policy.
+//!
+//! See <https://learn.microsoft.com/rest/api/storageservices/define-stored-access-policy>
+
+use crate::{
+    rest::RestClient,
+    sas::{format_time, parse_flags},
+    utils::{output, parse_time, TimeFormat},
+};
+use azure_core::{
+    date,
+    error::{Error, ErrorKind, Result},
+    headers::{Headers, CONTENT_TYPE, LAST_MODIFIED},
+    request_options::IfModifiedSinceCondition,
+    xml::to_xml,
+    Method, Url,
+};
+use azure_storage::clients::ServiceType;
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+
+/// The most stored access policies the service accepts on a resource
+const MAX_POLICIES: usize = 5;
+const MAX_ID_LENGTH: usize = 64;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "SignedIdentifiers")]
+struct SignedIdentifiers {
+    #[serde(rename = "SignedIdentifier", default)]
+    signed_identifiers: Vec<StoredAccessPolicy>,
+}
+
+/// A stored access policy, where each of the fields other than the id may
+/// instead be specified by the SAS that refers to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StoredAccessPolicy {
+    #[serde(rename = "Id")]
+    id: String,
+    #[serde(rename = "AccessPolicy", default)]
+    access_policy: AccessPolicy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AccessPolicy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permission: Option<String>,
+}
+
+/// Fields of a stored access policy
+#[derive(Debug, Args)]
+pub(crate) struct PolicyArgs {
+    /// Start time
+    #[clap(long)]
+    start: Option<String>,
+    /// Expiration
+    #[clap(long)]
+    expiry: Option<String>,
+    /// Format used for the start and expiry times
+    #[clap(long, value_enum, default_value_t = TimeFormat::Offset)]
+    time_format: TimeFormat,
+    /// Permissions, such as `racwdl` for a container, `raup` for a queue, or `raud` for a table
+    #[clap(long)]
+    permissions: Option<String>,
+}
+
+/// Change to the stored access policies of a resource
+#[derive(Debug, Subcommand)]
+pub(crate) enum PolicyChange {
+    /// Add a stored access policy
+    Add {
+        /// policy identifier, used as `--identifier` when generating a SAS
+        id: String,
+        #[clap(flatten)]
+        policy: PolicyArgs,
+    },
+    /// Update the specified fields of a stored access policy, keeping the others
+    Update {
+        /// policy identifier
+        id: String,
+        #[clap(flatten)]
+        policy: PolicyArgs,
+    },
+    /// Remove a stored access policy, revoking each SAS that refers to it
+    Remove {
+        /// policy identifier
+        id: String,
+    },
+}
+
+impl PolicyChange {
+    /// Apply the change, with permissions limited to the flags in `allowed`
+    pub(crate) fn apply(self, policies: &mut Vec<StoredAccessPolicy>, allowed: &str) -> Result<()> {
+        match self {
+            Self::Add { id, policy } => {
+                if id.is_empty() || id.len() > MAX_ID_LENGTH {
+                    return Err(Error::with_message(ErrorKind::DataConversion, || {
+                        format!("policy id must be between 1 and {MAX_ID_LENGTH} characters")
+                    }));
+                }
+                if policies.iter().any(|existing| existing.id == id) {
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!("policy `{id}` already exists")
+                    }));
+                }
+                if policies.len() >= MAX_POLICIES {
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!("at most {MAX_POLICIES} stored access policies are supported")
+                    }));
+                }
+                let mut access_policy = AccessPolicy::default();
+                policy.update(&mut access_policy, allowed)?;
+                policies.push(StoredAccessPolicy { id, access_policy });
+            }
+            Self::Update { id, policy } => {
+                let existing = policies
+                    .iter_mut()
+                    .find(|existing| existing.id == id)
+                    .ok_or_else(|| not_found(&id))?;
+                policy.update(&mut existing.access_policy, allowed)?;
+            }
+            Self::Remove { id } => {
+                let count = policies.len();
+                policies.retain(|existing| existing.id != id);
+                if policies.len() == count {
+                    return Err(not_found(&id));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PolicyArgs {
+    fn update(self, access_policy: &mut AccessPolicy, allowed: &str) -> Result<()> {
+        if let Some(start) = self.start {
+            access_policy.start = Some(format_time(parse_time(&start, self.time_format)?)?);
+        }
+        if let Some(expiry) = self.expiry {
+            access_policy.expiry = Some(format_time(parse_time(&expiry, self.time_format)?)?);
+        }
+        if let Some(permissions) = self.permissions {
+            access_policy.permission = Some(parse_flags(&permissions, allowed, "permission")?);
+        }
+        Ok(())
+    }
+}
+
+fn not_found(id: &str) -> Error {
+    Error::with_message(ErrorKind::Other, || format!("policy `{id}` does not exist"))
+}
+
+/// Stored access policies of a resource, along with the public access level
+/// of a container
+#[derive(Debug, Serialize)]
+pub(crate) struct Acl {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_access: Option<String>,
+    policies: Vec<Policy>,
+}
+
+#[derive(Debug, Serialize)]
+struct Policy {
+    id: String,
+    start: Option<String>,
+    expiry: Option<String>,
+    permission: Option<String>,
+}
+
+impl Acl {
+    pub(crate) fn new(public_access: Option<String>, policies: Vec<StoredAccessPolicy>) -> Self {
+        Self {
+            public_access,
+            policies: policies
+                .into_iter()
+                .map(|policy| Policy {
+                    id: policy.id,
+                    start: policy.access_policy.start,
+                    expiry: policy.access_policy.expiry,
+                    permission: policy.access_policy.permission,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Get the stored access policies of the resource at `url`, along with the
+/// response headers
+pub(crate) async fn get_acl(
+    rest_client: &RestClient,
+    service_type: ServiceType,
+    mut url: Url,
+) -> Result<(Headers, Vec<StoredAccessPolicy>)> {
+    url.query_pairs_mut().append_pair("comp", "acl");
+    let response = rest_client
+        .send(service_type, Method::Get, url, Headers::new(), None)
+        .await?;
+    let headers = response.headers().clone();
+    let body: SignedIdentifiers = response.xml().await?;
+    Ok((headers, body.signed_identifiers))
+}
+
+/// Conditions for setting the ACL that only match while the resource is
+/// unmodified since the response headers of [`get_acl`] were returned
+///
+/// Setting a container ACL only accepts the modification time conditions,
+/// not `If-Match`.  Queues and tables do not return a last modified time for
+/// their ACL, so their updates cannot be conditioned.
+pub(crate) fn unchanged_since(headers: &Headers) -> Result<Headers> {
+    let mut conditions = Headers::new();
+    conditions.add(
+        headers
+            .get_optional_str(&LAST_MODIFIED)
+            .map(date::parse_rfc1123)
+            .transpose()?
+            .map(IfModifiedSinceCondition::Unmodified),
+    );
+    Ok(conditions)
+}
+
+/// Replace the stored access policies of the resource at `url`
+pub(crate) async fn set_acl(
+    rest_client: &RestClient,
+    service_type: ServiceType,
+    mut url: Url,
+    mut headers: Headers,
+    policies: Vec<StoredAccessPolicy>,
+) -> Result<()> {
+    url.query_pairs_mut().append_pair("comp", "acl");
+    let body = to_xml(&SignedIdentifiers {
+        signed_identifiers: policies,
+    })?;
+    headers.insert(CONTENT_TYPE, "application/xml");
+    rest_client
+        .send(service_type, Method::Put, url, headers, Some(body.into()))
+        .await?;
+    Ok(())
+}
+
+/// Apply a change to the stored access policies of a queue or table, and
+/// output the resulting policies
+pub(crate) async fn update_acl(
+    rest_client: &RestClient,
+    service_type: ServiceType,
+    url: Url,
+    change: PolicyChange,
+    allowed: &str,
+) -> Result<()> {
+    let (headers, mut policies) = get_acl(rest_client, service_type, url.clone()).await?;
+    change.apply(&mut policies, allowed)?;
+    set_acl(
+        rest_client,
+        service_type,
+        url,
+        unchanged_since(&headers)?,
+        policies.clone(),
+    )
+    .await?;
+    output(&Acl::new(None, policies))
+}
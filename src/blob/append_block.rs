@@ -0,0 +1,185 @@
+use crate::{
+    args,
+    blob::encryption::Encryption,
+    utils::{http_status, parse_duration},
+};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    request_options::{IfMatchCondition, IfTags},
+    StatusCode,
+};
+use azure_storage_blobs::prelude::BlobClient;
+use std::{path::PathBuf, pin::Pin, time::Duration};
+use tokio::{
+    fs::File,
+    io::{stdin, AsyncRead, AsyncReadExt},
+    time::timeout,
+};
+use tracing::debug;
+use uuid::Uuid;
+
+/// The largest block accepted by Append Block
+const MAX_BLOCK_SIZE: u64 = 100 * 1024 * 1024;
+const DEFAULT_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Append the contents of the specified file to an existing "append blob" blob.
+#[derive(clap::Parser)]
+pub struct AppendBlock {
+    /// File to append to the blob (use `-` to read from STDIN)
+    path: PathBuf,
+    /// Split the contents into append blocks of this size
+    #[clap(long, default_value_t = DEFAULT_BLOCK_SIZE, value_parser = clap::value_parser!(u64).range(1..=MAX_BLOCK_SIZE))]
+    block_size: u64,
+    /// Append a partial block once no more input has arrived for this long
+    ///
+    /// Keeps slow streams such as logs piped to STDIN from waiting for a full block.
+    /// Duration as parsed by <https://docs.rs/duration-string/latest/duration_string/>
+    /// Examples include `500ms`, `1s`, and `1m`
+    #[clap(long, default_value = "1s")]
+    flush_interval: String,
+    /// Create the "append blob" if it does not already exist
+    #[clap(long)]
+    create_if_missing: bool,
+    #[clap(long)]
+    condition_max_size: Option<u64>,
+    /// Expected offset of the first block, which is advanced for each subsequent block
+    ///
+    /// Defaults to the length of the blob when the command starts, so the
+    /// blocks fail rather than interleave with blocks appended by other writers
+    #[clap(long)]
+    condition_append_position: Option<u64>,
+    #[clap(long)]
+    if_tags: Option<IfTags>,
+    #[clap(long)]
+    lease_id: Option<Uuid>,
+    #[clap(flatten)]
+    encryption: Encryption,
+    // #[clap(long)]
+    // if_modified_since: Option<IfModifiedSinceCondition>,
+    // #[clap(long)]
+    // if_match: Option<IfMatchCondition>,
+}
+
+impl AppendBlock {
+    pub async fn execute(self, blob_client: &BlobClient) -> Result<()> {
+        let AppendBlock {
+            path,
+            block_size,
+            flush_interval,
+            create_if_missing,
+            condition_max_size,
+            mut condition_append_position,
+            if_tags,
+            lease_id,
+            encryption,
+        } = self;
+        let context = encryption.context()?;
+        let flush_interval = parse_duration(&flush_interval)?;
+
+        let block_size =
+            usize::try_from(block_size).map_err(|e| Error::new(ErrorKind::DataConversion, e))?;
+
+        let mut handle: Pin<Box<dyn AsyncRead>> = if path.as_os_str() == "-" {
+            Box::pin(stdin())
+        } else {
+            Box::pin(File::open(path).await?)
+        };
+
+        let mut created = false;
+        if create_if_missing {
+            // Only create the blob if it does not exist, rather than
+            // truncating a blob created by another writer since checking
+            let response = blob_client
+                .put_append_blob()
+                .if_match(IfMatchCondition::NotMatch("*".to_string()))
+                .context(context.clone())
+                .await;
+            match response {
+                Ok(response) => {
+                    debug!("{response:#?}");
+                    created = true;
+                }
+                Err(err)
+                    if matches!(
+                        http_status(&err),
+                        Some(StatusCode::Conflict | StatusCode::PreconditionFailed)
+                    ) =>
+                {
+                    debug!("append blob already exists");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        if condition_append_position.is_none() {
+            condition_append_position = Some(if created {
+                0
+            } else {
+                blob_client
+                    .get_properties()
+                    .context(context.clone())
+                    .await?
+                    .blob
+                    .properties
+                    .content_length
+            });
+        }
+
+        loop {
+            let block = read_available(&mut handle, block_size, flush_interval).await?;
+            if block.is_empty() {
+                break;
+            }
+            let len = block.len() as u64;
+
+            let mut builder = blob_client.append_block(block).context(context.clone());
+            let if_tags = if_tags.clone();
+            args!(
+                builder,
+                condition_max_size,
+                condition_append_position,
+                if_tags,
+                lease_id
+            );
+            let response = builder.await?;
+            debug!("{response:#?}");
+
+            condition_append_position = condition_append_position.map(|x| x + len);
+        }
+
+        Ok(())
+    }
+}
+
+/// Read up to `block_size` bytes, returning a short block early when no more
+/// input arrives within `flush_interval` or at the end of the input
+///
+/// Waits indefinitely for the first byte, so an empty block marks the end of
+/// the input.
+async fn read_available<R>(
+    handle: &mut R,
+    block_size: usize,
+    flush_interval: Duration,
+) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut block = Vec::with_capacity(block_size);
+    while block.len() < block_size {
+        let mut take = (&mut *handle).take((block_size - block.len()) as u64);
+        let read = if block.is_empty() {
+            take.read_buf(&mut block).await?
+        } else {
+            // reads are cancel safe, so input arriving after the timeout is
+            // returned by the next read rather than lost
+            match timeout(flush_interval, take.read_buf(&mut block)).await {
+                Ok(read) => read?,
+                Err(_) => break,
+            }
+        };
+        if read == 0 {
+            break;
+        }
+    }
+    Ok(block)
+}
@@ -0,0 +1,280 @@
+//! Client-side envelope encryption, compatible with version 2.0 of the Azure
+//! Storage SDK client-side encryption format.
+//!
+//! Blob content is encrypted in regions of 4 MiB using AES-GCM-256 with a
+//! random content encryption key per blob. Each region is stored as
+//! `nonce || ciphertext || tag`. The content encryption key is wrapped by a
+//! key encryption key and stored, with the algorithm details, as JSON in the
+//! `encryptiondata` metadata entry of the blob.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng},
+    AeadCore, Aes256Gcm, Key, Nonce,
+};
+use aes_kw::KekAes256;
+use azure_core::{
+    base64,
+    error::{Error, ErrorKind, Result},
+};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Metadata entry used to store the encryption details
+pub(crate) const METADATA_KEY: &str = "encryptiondata";
+
+const PROTOCOL: &str = "2.0";
+const ENCRYPTION_ALGORITHM: &str = "AES_GCM_256";
+const AES_KEY_WRAP_ALGORITHM: &str = "A256KW";
+/// Amount of plaintext in each encrypted region
+pub(crate) const REGION_DATA_LENGTH: usize = 4 * 1024 * 1024;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+/// Provider of the key encryption key used to wrap each blob's content encryption key
+pub(crate) trait KeyEncryptionKey {
+    /// Identifier recorded alongside the wrapped key
+    fn key_id(&self) -> &str;
+    /// Name of the key wrapping algorithm, as recorded alongside the wrapped key
+    fn algorithm(&self) -> &str;
+    fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>>;
+    fn unwrap_key(&self, wrapped_key: &[u8], algorithm: &str) -> Result<Vec<u8>>;
+}
+
+/// AES-256 key encryption key stored in a local file
+pub(crate) struct LocalKeyEncryptionKey {
+    key_id: String,
+    kek: KekAes256,
+}
+
+impl KeyEncryptionKey for LocalKeyEncryptionKey {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn algorithm(&self) -> &str {
+        AES_KEY_WRAP_ALGORITHM
+    }
+
+    fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>> {
+        self.kek
+            .wrap_vec(key)
+            .map_err(|e| Error::with_message(ErrorKind::Other, || format!("wrap key: {e}")))
+    }
+
+    fn unwrap_key(&self, wrapped_key: &[u8], algorithm: &str) -> Result<Vec<u8>> {
+        if algorithm != AES_KEY_WRAP_ALGORITHM {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("unsupported key wrapping algorithm: {algorithm}")
+            }));
+        }
+        self.kek
+            .unwrap_vec(wrapped_key)
+            .map_err(|e| Error::with_message(ErrorKind::Other, || format!("unwrap key: {e}")))
+    }
+}
+
+/// Key encryption key options for client-side encryption
+#[derive(Debug, Clone, Args)]
+pub(crate) struct KeyEncryptionKeyArgs {
+    /// File containing the key encryption key for client-side encryption (a base64 encoded AES-256 key)
+    #[clap(long, env = "STORAGE_KEK_FILE")]
+    kek_file: Option<PathBuf>,
+    /// Identifier recorded with the wrapped key (defaults to the key file name)
+    #[clap(long, requires = "kek_file")]
+    kek_id: Option<String>,
+}
+
+impl KeyEncryptionKeyArgs {
+    pub(crate) fn load(&self) -> Result<Option<Box<dyn KeyEncryptionKey>>> {
+        let Some(kek_file) = &self.kek_file else {
+            return Ok(None);
+        };
+        let key = base64::decode(std::fs::read_to_string(kek_file)?.trim())?;
+        let key: [u8; KEY_LENGTH] = key.try_into().map_err(|key: Vec<u8>| {
+            Error::with_message(ErrorKind::DataConversion, || {
+                format!(
+                    "key encryption key must be {KEY_LENGTH} bytes, found {}",
+                    key.len()
+                )
+            })
+        })?;
+        let key_id = self.kek_id.clone().unwrap_or_else(|| {
+            kek_file
+                .file_name()
+                .unwrap_or(kek_file.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        });
+        Ok(Some(Box::new(LocalKeyEncryptionKey {
+            key_id,
+            kek: KekAes256::from(key),
+        })))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct EncryptionData {
+    wrapped_content_key: WrappedContentKey,
+    encryption_agent: EncryptionAgent,
+    encrypted_region_info: Option<EncryptedRegionInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_wrapping_metadata: Option<KeyWrappingMetadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WrappedContentKey {
+    key_id: String,
+    encrypted_key: String,
+    algorithm: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EncryptionAgent {
+    protocol: String,
+    encryption_algorithm: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EncryptedRegionInfo {
+    data_length: usize,
+    nonce_length: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct KeyWrappingMetadata {
+    encryption_library: String,
+}
+
+/// The protocol version is bound to the content encryption key by wrapping
+/// them together, with the version padded to 8 bytes.
+fn protocol_prefix() -> [u8; 8] {
+    let mut prefix = [0; 8];
+    for (dst, src) in prefix.iter_mut().zip(PROTOCOL.as_bytes()) {
+        *dst = *src;
+    }
+    prefix
+}
+
+pub(crate) struct RegionEncryptor {
+    cipher: Aes256Gcm,
+}
+
+impl RegionEncryptor {
+    /// Generate a new content encryption key, returning the encryptor and
+    /// the metadata value to store with the blob.
+    pub(crate) fn new(kek: &dyn KeyEncryptionKey) -> Result<(Self, String)> {
+        let key = Aes256Gcm::generate_key(OsRng);
+
+        let mut to_wrap = protocol_prefix().to_vec();
+        to_wrap.extend_from_slice(&key);
+        let wrapped = kek.wrap_key(&to_wrap)?;
+
+        let encryption_data = EncryptionData {
+            wrapped_content_key: WrappedContentKey {
+                key_id: kek.key_id().to_string(),
+                encrypted_key: base64::encode(wrapped),
+                algorithm: kek.algorithm().to_string(),
+            },
+            encryption_agent: EncryptionAgent {
+                protocol: PROTOCOL.to_string(),
+                encryption_algorithm: ENCRYPTION_ALGORITHM.to_string(),
+            },
+            encrypted_region_info: Some(EncryptedRegionInfo {
+                data_length: REGION_DATA_LENGTH,
+                nonce_length: NONCE_LENGTH,
+            }),
+            key_wrapping_metadata: Some(KeyWrappingMetadata {
+                encryption_library: format!("azs {}", env!("CARGO_PKG_VERSION")),
+            }),
+        };
+        let encryption_data = serde_json::to_string(&encryption_data)?;
+
+        Ok((
+            Self {
+                cipher: Aes256Gcm::new(&key),
+            },
+            encryption_data,
+        ))
+    }
+
+    /// Encrypt a region of at most `REGION_DATA_LENGTH` bytes
+    pub(crate) fn encrypt_region(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| Error::with_message(ErrorKind::Other, || format!("encrypt: {e}")))?;
+        let mut region = nonce.to_vec();
+        region.extend_from_slice(&ciphertext);
+        Ok(region)
+    }
+}
+
+pub(crate) struct RegionDecryptor {
+    cipher: Aes256Gcm,
+    region_length: usize,
+}
+
+impl RegionDecryptor {
+    pub(crate) fn new(encryption_data: &str, kek: &dyn KeyEncryptionKey) -> Result<Self> {
+        let encryption_data: EncryptionData = serde_json::from_str(encryption_data)?;
+
+        let EncryptionAgent {
+            protocol,
+            encryption_algorithm,
+        } = &encryption_data.encryption_agent;
+        if protocol != PROTOCOL || encryption_algorithm != ENCRYPTION_ALGORITHM {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("unsupported client-side encryption: protocol {protocol} using {encryption_algorithm}")
+            }));
+        }
+
+        let region_info = encryption_data.encrypted_region_info.ok_or_else(|| {
+            Error::message(ErrorKind::DataConversion, "missing encrypted region info")
+        })?;
+        if region_info.nonce_length != NONCE_LENGTH {
+            return Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("unsupported nonce length: {}", region_info.nonce_length)
+            }));
+        }
+
+        let wrapped = base64::decode(&encryption_data.wrapped_content_key.encrypted_key)?;
+        let unwrapped = kek.unwrap_key(&wrapped, &encryption_data.wrapped_content_key.algorithm)?;
+        let key = unwrapped
+            .strip_prefix(&protocol_prefix())
+            .filter(|key| key.len() == KEY_LENGTH)
+            .ok_or_else(|| {
+                Error::message(ErrorKind::DataConversion, "invalid content encryption key")
+            })?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            region_length: NONCE_LENGTH + region_info.data_length + TAG_LENGTH,
+        })
+    }
+
+    /// Size of each encrypted region, other than the last region of the blob
+    pub(crate) fn region_length(&self) -> usize {
+        self.region_length
+    }
+
+    pub(crate) fn decrypt_region(&self, region: &[u8]) -> Result<Vec<u8>> {
+        if region.len() < NONCE_LENGTH + TAG_LENGTH {
+            return Err(Error::message(
+                ErrorKind::DataConversion,
+                "encrypted region is truncated",
+            ));
+        }
+        let (nonce, ciphertext) = region.split_at(NONCE_LENGTH);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|e| Error::with_message(ErrorKind::Other, || format!("decrypt: {e}")))
+    }
+}
@@ -1,17 +1,25 @@
 use crate::{
     args,
-    utils::{parse_key_val, to_metadata, to_tags},
+    blob::{
+        client_encryption::{
+            KeyEncryptionKeyArgs, RegionEncryptor, METADATA_KEY, REGION_DATA_LENGTH,
+        },
+        encryption::Encryption,
+        get::DownloadOptions,
+    },
+    utils::{parse_key_val, read_block, to_metadata, to_tags},
 };
 use azure_core::{
     error::{Error, ErrorKind, Result},
-    request_options::IfTags,
+    request_options::{IfMatchCondition, IfTags, Metadata},
     tokio::fs::FileStreamBuilder,
 };
 use azure_storage_blobs::prelude::{
     AccessTier, BlobBlockType, BlobClient, BlobContentDisposition, BlobContentEncoding,
-    BlobContentLanguage, BlobContentType, BlockList,
+    BlobContentLanguage, BlobContentMD5, BlobContentType, BlockList, Hash,
 };
-use std::path::PathBuf;
+use clap::Args;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tracing::debug;
 use uuid::Uuid;
@@ -20,12 +28,6 @@ use uuid::Uuid;
 #[derive(clap::Parser)]
 pub struct CreateBlockBlob {
     path: PathBuf,
-    /// Upload the file in blocks of this size
-    #[clap(long)]
-    upload_block_size: Option<u64>,
-    /// How much to buffer in memory while uploading
-    #[clap(long)]
-    buffer_size: Option<usize>,
     #[clap(long)]
     content_type: Option<BlobContentType>,
     #[clap(long)]
@@ -34,42 +36,188 @@ pub struct CreateBlockBlob {
     content_language: Option<BlobContentLanguage>,
     #[clap(long)]
     content_disposition: Option<BlobContentDisposition>,
-    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
-    tags: Option<Vec<(String, String)>>,
-    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
-    metadata: Option<Vec<(String, String)>>,
     #[clap(long)]
     if_tags: Option<IfTags>,
     #[clap(long)]
     lease_id: Option<Uuid>,
-    #[clap(long)]
-    access_tier: Option<AccessTier>,
+    #[clap(flatten)]
+    options: BlockBlobOptions,
 }
+
 impl CreateBlockBlob {
     pub async fn execute(self, blob_client: &BlobClient) -> Result<()> {
         let CreateBlockBlob {
             path,
-            upload_block_size: block_size,
-            buffer_size,
             content_type,
             content_encoding,
             content_language,
             content_disposition,
-            tags,
-            metadata,
             if_tags,
             lease_id,
-            access_tier,
+            options,
         } = self;
-        let handle = File::open(path).await?;
-        let mut builder = FileStreamBuilder::new(handle);
-        args!(builder, buffer_size, block_size);
-        let mut handle = builder.build().await?;
+        let headers = BlobHeaders {
+            content_type,
+            content_encoding,
+            content_language,
+            content_disposition,
+            content_md5: None,
+            if_tags,
+            if_match: None,
+            lease_id,
+        };
+        options.upload(blob_client, &path, headers).await
+    }
+}
+
+/// Properties and conditions of a single upload
+#[derive(Default)]
+pub(crate) struct BlobHeaders {
+    pub content_type: Option<BlobContentType>,
+    pub content_encoding: Option<BlobContentEncoding>,
+    pub content_language: Option<BlobContentLanguage>,
+    pub content_disposition: Option<BlobContentDisposition>,
+    /// Stored as the blob's `Content-MD5`, which the service otherwise only
+    /// computes for uploads in a single request
+    pub content_md5: Option<BlobContentMD5>,
+    pub if_tags: Option<IfTags>,
+    /// Condition on the existing blob, such as not matching `*` to only
+    /// create it
+    pub if_match: Option<IfMatchCondition>,
+    pub lease_id: Option<Uuid>,
+}
 
-        let tags = tags.map(to_tags);
-        let metadata = metadata.map(to_metadata);
+/// Options for uploading files as block blobs
+#[derive(Debug, Args)]
+pub(crate) struct BlockBlobOptions {
+    /// Upload the file in blocks of this size
+    #[clap(long)]
+    upload_block_size: Option<u64>,
+    /// How much to buffer in memory while uploading
+    #[clap(long)]
+    buffer_size: Option<usize>,
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+    tags: Option<Vec<(String, String)>>,
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+    metadata: Option<Vec<(String, String)>>,
+    #[clap(long)]
+    access_tier: Option<AccessTier>,
+    #[clap(flatten)]
+    encryption: Encryption,
+    /// Encrypt the contents on this host before uploading
+    ///
+    /// Each encrypted region is uploaded as its own block, so the block and
+    /// buffer sizes cannot be set.
+    #[clap(long, requires = "kek_file", conflicts_with_all = ["upload_block_size", "buffer_size"])]
+    client_encrypt: bool,
+    #[clap(flatten)]
+    kek: KeyEncryptionKeyArgs,
+}
+
+impl BlockBlobOptions {
+    /// Whether the contents are encrypted before uploading, in which case the
+    /// stored `Content-MD5` cannot match the local file
+    pub(crate) fn client_encrypt(&self) -> bool {
+        self.client_encrypt
+    }
+
+    /// Options to read back blobs written with these options
+    pub(crate) fn download_options(&self) -> DownloadOptions {
+        DownloadOptions::new(
+            self.encryption.customer_provided_key().clone(),
+            self.kek.clone(),
+        )
+    }
+
+    #[allow(clippy::too_many_lines)]
+    pub(crate) async fn upload(
+        &self,
+        blob_client: &BlobClient,
+        path: &Path,
+        headers: BlobHeaders,
+    ) -> Result<()> {
+        let BlobHeaders {
+            content_type,
+            content_encoding,
+            content_language,
+            content_disposition,
+            content_md5,
+            if_tags,
+            if_match,
+            lease_id,
+        } = headers;
+        let block_size = self.upload_block_size;
+        let buffer_size = self.buffer_size;
+        let access_tier = self.access_tier;
+        let context = self.encryption.context()?;
+        let tags = self.tags.clone().map(to_tags);
+        let mut metadata = self.metadata.clone().map(to_metadata);
+
+        let block_list = if self.client_encrypt {
+            let kek = self.kek.load()?.ok_or_else(|| {
+                Error::message(
+                    ErrorKind::Other,
+                    "client-side encryption requires a key encryption key",
+                )
+            })?;
+            let (encryptor, encryption_data) = RegionEncryptor::new(kek.as_ref())?;
+            metadata
+                .get_or_insert_with(Metadata::new)
+                .insert(METADATA_KEY, encryption_data);
+
+            // Each encrypted region is uploaded as a block, with ids of the
+            // same length for every offset, as the service requires
+            let mut handle = File::open(path).await?;
+            let mut block_list = BlockList::default();
+            let mut offset: u64 = 0;
+            loop {
+                let region = read_block(&mut handle, REGION_DATA_LENGTH).await?;
+                if region.is_empty() {
+                    break;
+                }
+                let block_id = format!("{offset:016X}");
+                let mut builder = blob_client
+                    .put_block(block_id.clone(), encryptor.encrypt_region(&region)?)
+                    .context(context.clone());
+                args!(builder, lease_id);
+                let response = builder.await?;
+                debug!("{response:#?}");
+                block_list
+                    .blocks
+                    .push(BlobBlockType::new_uncommitted(block_id));
+                offset += region.len() as u64;
+            }
+            block_list
+        } else {
+            let handle = File::open(path).await?;
+            let mut builder = FileStreamBuilder::new(handle);
+            args!(builder, buffer_size, block_size);
+            let mut handle = builder.build().await?;
+
+            let Some(block_size) = block_size else {
+                let mut builder = blob_client.put_block_blob(handle).context(context);
+                let hash = content_md5.map(|content_md5| Hash::MD5(content_md5.0));
+
+                args!(
+                    builder,
+                    hash,
+                    if_tags,
+                    if_match,
+                    lease_id,
+                    content_type,
+                    content_language,
+                    content_disposition,
+                    content_encoding,
+                    access_tier,
+                    tags,
+                    metadata
+                );
+
+                let response = builder.await?;
+                debug!("{response:#?}");
+                return Ok(());
+            };
 
-        if let Some(block_size) = block_size {
             let mut block_list = BlockList::default();
 
             for offset in (handle.offset..handle.stream_size).step_by(
@@ -77,7 +225,9 @@ impl CreateBlockBlob {
                     .map_err(|e| Error::new(ErrorKind::DataConversion, e))?,
             ) {
                 let block_id = format!("{offset:08X}");
-                let mut builder = blob_client.put_block(block_id.clone(), &handle);
+                let mut builder = blob_client
+                    .put_block(block_id.clone(), &handle)
+                    .context(context.clone());
                 args!(builder, lease_id);
                 let response = builder.await?;
                 debug!("{response:#?}");
@@ -86,40 +236,26 @@ impl CreateBlockBlob {
                     .push(BlobBlockType::new_uncommitted(block_id));
                 handle.next_block().await?;
             }
-            let mut builder = blob_client.put_block_list(block_list);
-            args!(
-                builder,
-                if_tags,
-                lease_id,
-                content_type,
-                content_language,
-                content_disposition,
-                content_encoding,
-                access_tier,
-                tags,
-                metadata
-            );
-            let response = builder.await?;
-            debug!("{response:#?}");
-        } else {
-            let mut builder = blob_client.put_block_blob(handle);
+            block_list
+        };
 
-            args!(
-                builder,
-                if_tags,
-                lease_id,
-                content_type,
-                content_language,
-                content_disposition,
-                content_encoding,
-                access_tier,
-                tags,
-                metadata
-            );
-
-            let response = builder.await?;
-            debug!("{response:#?}");
-        }
+        let mut builder = blob_client.put_block_list(block_list).context(context);
+        args!(
+            builder,
+            content_md5,
+            if_tags,
+            if_match,
+            lease_id,
+            content_type,
+            content_language,
+            content_disposition,
+            content_encoding,
+            access_tier,
+            tags,
+            metadata
+        );
+        let response = builder.await?;
+        debug!("{response:#?}");
         Ok(())
     }
 }
@@ -1,5 +1,6 @@
 use crate::{
     args,
+    blob::encryption::Encryption,
     utils::{parse_key_val, round_up, to_metadata, to_tags},
 };
 use azure_core::error::{Error, ErrorKind, Result};
@@ -33,6 +34,8 @@ pub struct CreatePageBlob {
     sequence_number: Option<u64>,
     #[clap(long)]
     upload_block_size: Option<usize>,
+    #[clap(flatten)]
+    encryption: Encryption,
 }
 impl CreatePageBlob {
     pub async fn execute(self, blob_client: &BlobClient) -> Result<()> {
@@ -47,7 +50,9 @@ impl CreatePageBlob {
             lease_id,
             sequence_number,
             upload_block_size,
+            encryption,
         } = self;
+        let context = encryption.context()?;
         let tags = tags.map(to_tags);
         let metadata = metadata.map(to_metadata);
 
@@ -55,7 +60,9 @@ impl CreatePageBlob {
         let length = handle.metadata().await?.len();
 
         let rounded_up = round_up(length, 512);
-        let mut builder = blob_client.put_page_blob(u128::from(rounded_up));
+        let mut builder = blob_client
+            .put_page_blob(u128::from(rounded_up))
+            .context(context.clone());
         args!(
             builder,
             content_type,
@@ -86,7 +93,9 @@ impl CreatePageBlob {
             handle = take_handle.into_inner();
 
             let ba512_range = BA512Range::new(start, start + rounded_up - 1)?;
-            let mut builder = blob_client.put_page(ba512_range, buf);
+            let mut builder = blob_client
+                .put_page(ba512_range, buf)
+                .context(context.clone());
             args!(builder, lease_id);
             let response = builder.await?;
             debug!("{response:#?}");
@@ -0,0 +1,95 @@
+use azure_core::{
+    base64,
+    error::{Error, ErrorKind, Result},
+    headers::Headers,
+    CustomHeaders,
+};
+use azure_storage_blobs::prelude::{CPKInfo, EncryptionScope};
+use clap::Args;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Length of the AES-256 keys supported as customer-provided keys
+const KEY_LENGTH: usize = 32;
+
+/// Customer-provided key used by the service to encrypt the blob
+#[derive(Debug, Clone, Args)]
+pub(crate) struct CustomerProvidedKey {
+    /// Customer-provided AES-256 key, base64 encoded
+    #[clap(long, env = "STORAGE_ENCRYPTION_KEY", hide_env_values = true)]
+    encryption_key: Option<String>,
+    /// File containing the customer-provided AES-256 key, base64 encoded
+    #[clap(long, conflicts_with = "encryption_key")]
+    encryption_key_file: Option<PathBuf>,
+}
+
+impl CustomerProvidedKey {
+    fn cpk_info(&self) -> Result<Option<CPKInfo>> {
+        let key = match (&self.encryption_key, &self.encryption_key_file) {
+            (Some(key), _) => key.trim().to_string(),
+            (None, Some(path)) => std::fs::read_to_string(path)?.trim().to_string(),
+            (None, None) => return Ok(None),
+        };
+
+        let decoded = base64::decode(&key)?;
+        if decoded.len() != KEY_LENGTH {
+            return Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!(
+                    "encryption key must be {KEY_LENGTH} bytes, found {}",
+                    decoded.len()
+                )
+            }));
+        }
+        let key_sha256 = base64::encode(Sha256::digest(&decoded));
+
+        Ok(Some(CPKInfo::new(key, key_sha256, None)))
+    }
+
+    pub(crate) fn headers(&self) -> Result<Headers> {
+        let mut headers = Headers::new();
+        headers.add(self.cpk_info()?);
+        Ok(headers)
+    }
+
+    /// Request context that adds the encryption headers to each request
+    pub(crate) fn context(&self) -> Result<azure_core::Context> {
+        Ok(to_context(self.headers()?))
+    }
+}
+
+/// Server-side encryption options for operations that write blob content
+#[derive(Debug, Args)]
+pub(crate) struct Encryption {
+    #[clap(flatten)]
+    key: CustomerProvidedKey,
+    /// Encryption scope used by the service to encrypt the blob
+    #[clap(long, conflicts_with_all = ["encryption_key", "encryption_key_file"])]
+    encryption_scope: Option<String>,
+}
+
+impl Encryption {
+    /// The customer-provided key, which is also required to read the blob
+    pub(crate) fn customer_provided_key(&self) -> &CustomerProvidedKey {
+        &self.key
+    }
+
+    pub(crate) fn headers(&self) -> Result<Headers> {
+        let mut headers = self.key.headers()?;
+        headers.add(self.encryption_scope.clone().map(EncryptionScope::from));
+        Ok(headers)
+    }
+
+    /// Request context that adds the encryption headers to each request
+    pub(crate) fn context(&self) -> Result<azure_core::Context> {
+        Ok(to_context(self.headers()?))
+    }
+}
+
+/// The SDK request builders only support encryption options on some
+/// operations, so the headers are added by the pipeline's custom headers
+/// policy instead.
+fn to_context(headers: Headers) -> azure_core::Context {
+    let mut context = azure_core::Context::new();
+    context.insert(CustomHeaders::from(headers));
+    context
+}
@@ -1,14 +1,13 @@
 use crate::{
     args,
+    sas::SasPermissions,
     utils::{parse_time, Protocol, TimeFormat},
 };
 use azure_core::error::Result;
 use azure_storage::shared_access_signature::{service_sas::BlobSasPermissions, SasProtocol};
 use azure_storage_blobs::prelude::BlobClient;
-use clap::Args;
 use time::OffsetDateTime;
 
-#[allow(clippy::struct_excessive_bools)]
 #[derive(clap::Parser)]
 pub struct GenerateSas {
     /// Expiration
@@ -31,38 +30,6 @@ pub struct GenerateSas {
     sas_permissions: SasPermissions,
 }
 
-#[derive(Debug, Args)]
-#[group(required = true)]
-#[allow(clippy::struct_excessive_bools)]
-struct SasPermissions {
-    #[clap(long)]
-    read: bool,
-    #[clap(long)]
-    add: bool,
-    #[clap(long)]
-    create: bool,
-    #[clap(long)]
-    write: bool,
-    #[clap(long)]
-    delete: bool,
-    #[clap(long)]
-    delete_version: bool,
-    #[clap(long)]
-    list: bool,
-    #[clap(long)]
-    tags: bool,
-    #[clap(long, name = "move")]
-    move_: bool,
-    #[clap(long)]
-    execute: bool,
-    #[clap(long)]
-    ownership: bool,
-    #[clap(long)]
-    permissions: bool,
-    #[clap(long)]
-    permanent_delete: bool,
-}
-
 impl GenerateSas {
     pub async fn execute(self, blob_client: &BlobClient) -> Result<()> {
         let GenerateSas {
@@ -72,22 +39,7 @@ impl GenerateSas {
             ip,
             identifier,
             protocol,
-            sas_permissions:
-                SasPermissions {
-                    read,
-                    add,
-                    create,
-                    write,
-                    delete,
-                    delete_version,
-                    list,
-                    tags,
-                    move_,
-                    execute,
-                    ownership,
-                    permissions,
-                    permanent_delete,
-                },
+            sas_permissions,
         } = self;
         let expiry = parse_time(&expiry, time_format)?;
         let start = start
@@ -95,21 +47,7 @@ impl GenerateSas {
             .transpose()?
             .unwrap_or_else(OffsetDateTime::now_utc);
 
-        let permissions = BlobSasPermissions {
-            read,
-            add,
-            create,
-            write,
-            delete,
-            delete_version,
-            permanent_delete,
-            list,
-            tags,
-            move_,
-            execute,
-            ownership,
-            permissions,
-        };
+        let permissions = BlobSasPermissions::try_from(sas_permissions)?;
 
         let user_deligation_key = blob_client
             .container_client()
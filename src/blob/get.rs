@@ -1,9 +1,16 @@
-use crate::args;
+use crate::{
+    args,
+    blob::{
+        client_encryption::{KeyEncryptionKeyArgs, RegionDecryptor, METADATA_KEY},
+        encryption::CustomerProvidedKey,
+    },
+};
 use azure_core::{
-    error::Result,
+    error::{Error, ErrorKind, Result},
     request_options::{IfTags, LeaseId},
 };
-use azure_storage_blobs::prelude::BlobClient;
+use azure_storage_blobs::prelude::{BlobClient, BlobVersioning};
+use clap::Args;
 use futures::StreamExt;
 use std::{path::PathBuf, pin::Pin};
 use tokio::{
@@ -19,17 +26,15 @@ pub struct Get {
     // #[clap(long)]
     // version_id: Option<String>,
     // #[clap(long)]
-    // encryption_key: Option<CPKInfo>,
-    // #[clap(long)]
     // if_modified_since: Option<IfModifiedSinceCondition>,
     // #[clap(long)]
     // if_match: Option<IfMatchCondition>,
     #[clap(long)]
     lease_id: Option<LeaseId>,
     #[clap(long)]
-    chunk_size: Option<u64>,
-    #[clap(long)]
     if_tags: Option<IfTags>,
+    #[clap(flatten)]
+    options: DownloadOptions,
 
     /// Where should the contents of the file be written (otherwise, written to STDOUT)
     destination: Option<PathBuf>,
@@ -39,28 +44,134 @@ impl Get {
     pub async fn execute(self, blob_client: &BlobClient) -> Result<()> {
         let Get {
             lease_id,
-            chunk_size,
             if_tags,
+            options,
             destination,
         } = self;
-        let mut builder = blob_client.get();
-        args!(builder, lease_id, chunk_size, if_tags);
 
-        let mut handle: Pin<Box<dyn AsyncWrite>> = if let Some(destination) = destination {
+        let handle: Pin<Box<dyn AsyncWrite>> = if let Some(destination) = destination {
             Box::pin(File::create(destination).await?)
         } else {
             Box::pin(stdout())
         };
 
+        let conditions = GetConditions {
+            lease_id,
+            if_tags,
+            blob_versioning: None,
+        };
+        options.download(blob_client, conditions, handle).await
+    }
+}
+
+/// Conditions and version of a single download
+#[derive(Default)]
+pub(crate) struct GetConditions {
+    pub lease_id: Option<LeaseId>,
+    pub if_tags: Option<IfTags>,
+    pub blob_versioning: Option<BlobVersioning>,
+}
+
+/// Options for downloading the contents of blobs
+#[derive(Debug, Args)]
+pub(crate) struct DownloadOptions {
+    #[clap(long)]
+    chunk_size: Option<u64>,
+    #[clap(flatten)]
+    encryption: CustomerProvidedKey,
+    /// Require the blob to be encrypted on the client
+    ///
+    /// Blobs encrypted on the client are always decrypted, using the key encryption key.
+    #[clap(long, requires = "kek_file")]
+    client_encrypt: bool,
+    #[clap(flatten)]
+    kek: KeyEncryptionKeyArgs,
+}
+
+impl DownloadOptions {
+    pub(crate) fn new(encryption: CustomerProvidedKey, kek: KeyEncryptionKeyArgs) -> Self {
+        Self {
+            chunk_size: None,
+            encryption,
+            client_encrypt: false,
+            kek,
+        }
+    }
+
+    pub(crate) async fn download(
+        &self,
+        blob_client: &BlobClient,
+        conditions: GetConditions,
+        mut handle: Pin<Box<dyn AsyncWrite + '_>>,
+    ) -> Result<()> {
+        let GetConditions {
+            lease_id,
+            if_tags,
+            blob_versioning,
+        } = conditions;
+        let chunk_size = self.chunk_size;
+        let mut builder = blob_client.get().context(self.encryption.context()?);
+        args!(builder, lease_id, chunk_size, if_tags, blob_versioning);
+
+        let mut decryptor = None;
+        let mut encrypted = vec![];
         let mut stream = builder.into_stream();
+        let mut first = true;
         while let Some(blob_entry) = stream.next().await {
             let mut blob_entry = blob_entry?;
             debug!("{blob_entry:#?}");
+            if first {
+                first = false;
+                let encryption_data = blob_entry
+                    .blob
+                    .metadata
+                    .as_ref()
+                    .and_then(|x| x.get(METADATA_KEY));
+                decryptor = match encryption_data {
+                    Some(encryption_data) => {
+                        let kek = self.kek.load()?.ok_or_else(|| {
+                            Error::message(
+                                ErrorKind::Other,
+                                "blob is encrypted on the client, which requires a key encryption key",
+                            )
+                        })?;
+                        Some(RegionDecryptor::new(encryption_data, kek.as_ref())?)
+                    }
+                    None if self.client_encrypt => {
+                        return Err(Error::message(
+                            ErrorKind::Other,
+                            "blob is not encrypted on the client",
+                        ));
+                    }
+                    None => None,
+                };
+            }
+
             while let Some(chunk) = blob_entry.data.next().await {
                 let chunk = chunk?;
-                handle.write_all(&chunk).await?;
+                let Some(decryptor) = &decryptor else {
+                    handle.write_all(&chunk).await?;
+                    continue;
+                };
+                encrypted.extend_from_slice(&chunk);
+                while encrypted.len() >= decryptor.region_length() {
+                    let region = encrypted
+                        .drain(..decryptor.region_length())
+                        .collect::<Vec<_>>();
+                    handle
+                        .write_all(&decryptor.decrypt_region(&region)?)
+                        .await?;
+                }
+            }
+        }
+        if let Some(decryptor) = &decryptor {
+            if !encrypted.is_empty() {
+                handle
+                    .write_all(&decryptor.decrypt_region(&encrypted)?)
+                    .await?;
             }
         }
+        handle.flush().await?;
         Ok(())
     }
 }
@@ -0,0 +1,285 @@
+use crate::{args, rest::RestClient};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    headers::Headers,
+    request_options::LeaseId,
+    AppendToUrlQuery, Method,
+};
+use azure_storage::clients::ServiceType;
+use azure_storage_blobs::{
+    blob::BlobType,
+    prelude::{BlobClient, BlobVersioning, Snapshot},
+};
+use clap::builder::RangedU64ValueParser;
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use std::{io::SeekFrom, path::PathBuf};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tracing::debug;
+
+const DEFAULT_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Download a "page blob", only fetching the page ranges that contain data.
+///
+/// The destination file is created sparse, with the regions without data left as holes.
+#[derive(clap::Parser)]
+pub struct GetPageBlob {
+    /// Where should the contents of the page blob be written
+    destination: PathBuf,
+    /// Download the specified snapshot of the blob
+    #[clap(long)]
+    snapshot: Option<Snapshot>,
+    /// Only apply the changes since the specified snapshot to an existing local image
+    ///
+    /// The destination must contain the contents of the blob as of the previous snapshot.
+    #[clap(long)]
+    previous_snapshot: Option<Snapshot>,
+    #[clap(long)]
+    lease_id: Option<LeaseId>,
+    /// Download the page ranges in chunks of this size
+    #[clap(long, default_value_t = DEFAULT_CHUNK_SIZE, value_parser = clap::value_parser!(u64).range(1..))]
+    chunk_size: u64,
+    /// Number of chunks to download concurrently
+    #[clap(long, default_value_t = DEFAULT_CONCURRENCY, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageList {
+    #[serde(rename = "$value", default)]
+    entries: Vec<PageListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+enum PageListEntry {
+    PageRange(PageListRange),
+    ClearRange(PageListRange),
+    /// Marker for the next page of ranges, which is empty on the last page
+    NextMarker(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PageListRange {
+    start: u64,
+    end: u64,
+}
+
+impl PageListRange {
+    /// Page list ranges are inclusive of the last byte
+    fn to_range(&self) -> std::ops::Range<u64> {
+        self.start..self.end + 1
+    }
+}
+
+impl GetPageBlob {
+    pub async fn execute(self, blob_client: &BlobClient, rest_client: &RestClient) -> Result<()> {
+        let GetPageBlob {
+            destination,
+            snapshot,
+            previous_snapshot,
+            lease_id,
+            chunk_size,
+            concurrency,
+        } = self;
+
+        let mut builder = blob_client.get_properties();
+        let blob_versioning = snapshot.clone().map(BlobVersioning::Snapshot);
+        args!(builder, lease_id, blob_versioning);
+        let properties = builder.await?.blob.properties;
+        if properties.blob_type != BlobType::PageBlob {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("expected a page blob, found {:?}", properties.blob_type)
+            }));
+        }
+
+        let (valid, clear) = get_page_ranges(
+            blob_client,
+            rest_client,
+            snapshot.as_ref(),
+            previous_snapshot.as_ref(),
+            lease_id,
+        )
+        .await?;
+
+        let total = valid.iter().map(|x| x.end - x.start).sum::<u64>();
+        debug!(
+            "{} valid ranges ({total} bytes), {} cleared ranges",
+            valid.len(),
+            clear.len()
+        );
+
+        let mut handle = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(previous_snapshot.is_none())
+            .truncate(previous_snapshot.is_none())
+            .open(&destination)
+            .await?;
+
+        for range in clear {
+            clear_range(&mut handle, range).await?;
+        }
+
+        let chunks = valid.into_iter().flat_map(|range| {
+            range
+                .clone()
+                .step_by(usize::try_from(chunk_size).unwrap_or(usize::MAX))
+                .map(move |start| start..range.end.min(start.saturating_add(chunk_size)))
+        });
+
+        let mut downloads = stream::iter(chunks)
+            .map(|range| get_range(blob_client, snapshot.as_ref(), lease_id, range))
+            .buffer_unordered(concurrency);
+
+        while let Some(result) = downloads.next().await {
+            let (offset, data) = result?;
+            handle.seek(SeekFrom::Start(offset)).await?;
+            handle.write_all(&data).await?;
+        }
+
+        // Writing past the end of the file and then extending it leaves holes
+        // rather than allocating the space, so only the ranges written above
+        // take up space on disk.
+        handle.set_len(properties.content_length).await?;
+        handle.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// Zero a range of an existing local image that was cleared since the
+/// previous snapshot
+///
+/// Only the parts that are not already zero are written, which leaves
+/// existing holes unallocated.
+async fn clear_range(handle: &mut File, range: std::ops::Range<u64>) -> Result<()> {
+    let mut buffer = vec![0; usize_from(DEFAULT_CHUNK_SIZE)?];
+    let mut offset = range.start;
+    while offset < range.end {
+        let step = (range.end - offset).min(DEFAULT_CHUNK_SIZE);
+        let len = usize_from(step)?;
+        let Some(chunk) = buffer.get_mut(..len) else {
+            break;
+        };
+        handle.seek(SeekFrom::Start(offset)).await?;
+        let mut read = 0;
+        while read < len {
+            let Some(rest) = chunk.get_mut(read..) else {
+                break;
+            };
+            match handle.read(rest).await? {
+                0 => break,
+                count => read += count,
+            }
+        }
+        if chunk.iter().take(read).any(|byte| *byte != 0) {
+            chunk.fill(0);
+            handle.seek(SeekFrom::Start(offset)).await?;
+            handle
+                .write_all(chunk.get(..read).unwrap_or_default())
+                .await?;
+        }
+        offset += step;
+    }
+    Ok(())
+}
+
+fn usize_from(value: u64) -> Result<usize> {
+    usize::try_from(value).map_err(|e| Error::new(ErrorKind::DataConversion, e))
+}
+
+async fn get_range(
+    blob_client: &BlobClient,
+    snapshot: Option<&Snapshot>,
+    lease_id: Option<LeaseId>,
+    range: std::ops::Range<u64>,
+) -> Result<(u64, Vec<u8>)> {
+    let offset = range.start;
+    let chunk_size = range.end - range.start;
+    let mut builder = blob_client.get().range(range).chunk_size(chunk_size);
+    let blob_versioning = snapshot.cloned().map(BlobVersioning::Snapshot);
+    args!(builder, lease_id, blob_versioning);
+
+    let mut data = Vec::with_capacity(usize_from(chunk_size)?);
+    let mut stream = builder.into_stream();
+    while let Some(response) = stream.next().await {
+        let mut body = response?.data;
+        while let Some(chunk) = body.next().await {
+            data.extend_from_slice(&chunk?);
+        }
+    }
+    Ok((offset, data))
+}
+
+/// The valid and cleared ranges of the blob, or of the changes since the
+/// previous snapshot, following continuation markers until every range has
+/// been listed
+///
+/// The SDK neither supports the `prevsnapshot` parameter of Get Page Ranges
+/// nor continues from the markers of a paged list, so the ranges are
+/// requested directly.
+async fn get_page_ranges(
+    blob_client: &BlobClient,
+    rest_client: &RestClient,
+    snapshot: Option<&Snapshot>,
+    previous_snapshot: Option<&Snapshot>,
+    lease_id: Option<LeaseId>,
+) -> Result<(Vec<std::ops::Range<u64>>, Vec<std::ops::Range<u64>>)> {
+    let previous_snapshot = previous_snapshot
+        .map(
+            |previous_snapshot| match serde_json::to_value(previous_snapshot)? {
+                serde_json::Value::String(id) => Ok(id),
+                _ => Err(Error::message(
+                    ErrorKind::DataConversion,
+                    "invalid snapshot",
+                )),
+            },
+        )
+        .transpose()?;
+
+    let mut valid = vec![];
+    let mut clear = vec![];
+    let mut marker: Option<String> = None;
+    loop {
+        let mut url = blob_client.url()?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("comp", "pagelist");
+            if let Some(previous_snapshot) = &previous_snapshot {
+                query.append_pair("prevsnapshot", previous_snapshot);
+            }
+            if let Some(marker) = &marker {
+                query.append_pair("marker", marker);
+            }
+        }
+        snapshot.append_to_url_query(&mut url);
+
+        let mut headers = Headers::new();
+        headers.add(lease_id);
+
+        let page_list: PageList = rest_client
+            .send(ServiceType::Blob, Method::Get, url, headers, None)
+            .await?
+            .xml()
+            .await?;
+
+        marker = None;
+        for entry in page_list.entries {
+            match entry {
+                PageListEntry::PageRange(range) => valid.push(range.to_range()),
+                PageListEntry::ClearRange(range) => clear.push(range.to_range()),
+                PageListEntry::NextMarker(next_marker) => {
+                    marker = Some(next_marker).filter(|marker| !marker.is_empty());
+                }
+            }
+        }
+        if marker.is_none() {
+            return Ok((valid, clear));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::rest::RestClient;
+use azure_core::{
+    date,
+    error::{Error, ErrorKind, Result},
+    headers::{HeaderName, Headers},
+    Method,
+};
+use azure_storage::clients::ServiceType;
+use azure_storage_blobs::prelude::BlobClient;
+use clap::ValueEnum;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+const IMMUTABILITY_POLICY_UNTIL_DATE: HeaderName =
+    HeaderName::from_static("x-ms-immutability-policy-until-date");
+const IMMUTABILITY_POLICY_MODE: HeaderName =
+    HeaderName::from_static("x-ms-immutability-policy-mode");
+const LEGAL_HOLD: HeaderName = HeaderName::from_static("x-ms-legal-hold");
+
+/// Whether a time-based immutability policy can still be changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+pub enum ImmutabilityPolicyMode {
+    /// The retention period can be extended or shortened, and the policy deleted
+    Unlocked,
+    /// The retention period can only be extended
+    Locked,
+    /// The policy no longer protects the blob, as reported by the service
+    /// rather than set
+    #[value(skip)]
+    Mutable,
+}
+
+impl ImmutabilityPolicyMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Unlocked => "Unlocked",
+            Self::Locked => "Locked",
+            Self::Mutable => "Mutable",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "unlocked" => Ok(Self::Unlocked),
+            "locked" => Ok(Self::Locked),
+            "mutable" => Ok(Self::Mutable),
+            _ => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("unknown immutability policy mode: {s}")
+            })),
+        }
+    }
+}
+
+/// Immutability policy and legal hold on a blob
+#[derive(Debug, Serialize)]
+pub struct ImmutabilityState {
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub policy_until: Option<OffsetDateTime>,
+    pub policy_mode: Option<ImmutabilityPolicyMode>,
+    pub legal_hold: Option<bool>,
+}
+
+impl ImmutabilityState {
+    pub(crate) fn from_headers(headers: &Headers) -> Result<Self> {
+        let policy_until = headers
+            .get_optional_str(&IMMUTABILITY_POLICY_UNTIL_DATE)
+            .map(date::parse_rfc1123)
+            .transpose()?;
+        let policy_mode = headers
+            .get_optional_str(&IMMUTABILITY_POLICY_MODE)
+            .map(ImmutabilityPolicyMode::parse)
+            .transpose()?;
+        let legal_hold = headers.get_optional_as(&LEGAL_HOLD)?;
+        Ok(Self {
+            policy_until,
+            policy_mode,
+            legal_hold,
+        })
+    }
+}
+
+pub(crate) async fn set_immutability_policy(
+    blob_client: &BlobClient,
+    rest_client: &RestClient,
+    until: OffsetDateTime,
+    mode: ImmutabilityPolicyMode,
+) -> Result<ImmutabilityState> {
+    let mut url = blob_client.url()?;
+    url.query_pairs_mut()
+        .append_pair("comp", "immutabilityPolicies");
+
+    let mut headers = Headers::new();
+    headers.insert(IMMUTABILITY_POLICY_UNTIL_DATE, date::to_rfc1123(&until));
+    headers.insert(IMMUTABILITY_POLICY_MODE, mode.as_str());
+
+    let response = rest_client
+        .send(ServiceType::Blob, Method::Put, url, headers, None)
+        .await?;
+    ImmutabilityState::from_headers(response.headers())
+}
+
+pub(crate) async fn delete_immutability_policy(
+    blob_client: &BlobClient,
+    rest_client: &RestClient,
+) -> Result<()> {
+    let mut url = blob_client.url()?;
+    url.query_pairs_mut()
+        .append_pair("comp", "immutabilityPolicies");
+
+    rest_client
+        .send(ServiceType::Blob, Method::Delete, url, Headers::new(), None)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn set_legal_hold(
+    blob_client: &BlobClient,
+    rest_client: &RestClient,
+    legal_hold: bool,
+) -> Result<ImmutabilityState> {
+    let mut url = blob_client.url()?;
+    url.query_pairs_mut().append_pair("comp", "legalhold");
+
+    let mut headers = Headers::new();
+    headers.insert(LEGAL_HOLD, legal_hold.to_string());
+
+    let response = rest_client
+        .send(ServiceType::Blob, Method::Put, url, headers, None)
+        .await?;
+    ImmutabilityState::from_headers(response.headers())
+}
@@ -1,15 +1,32 @@
-mod create_block_blob;
+mod append_block;
+pub(crate) mod client_encryption;
+pub(crate) mod create_block_blob;
 mod create_page_blob;
+mod encryption;
 mod generate_sas;
-mod get;
+pub(crate) mod get;
+mod get_page_blob;
+mod immutability;
+mod properties;
+mod tail;
 
 use crate::{
     args,
+    blob::append_block::AppendBlock,
     blob::create_block_blob::CreateBlockBlob,
     blob::create_page_blob::CreatePageBlob,
+    blob::encryption::{CustomerProvidedKey, Encryption},
     blob::generate_sas::GenerateSas,
     blob::get::Get,
-    utils::{parse_key_val, parse_time, to_metadata, to_tags, TimeFormat},
+    blob::get_page_blob::GetPageBlob,
+    blob::immutability::{
+        delete_immutability_policy, set_immutability_policy, set_legal_hold, ImmutabilityPolicyMode,
+    },
+    blob::properties::get_blob_properties,
+    blob::tail::Tail,
+    lease_exec::{LeaseExec, LeaseTarget},
+    rest::RestClient,
+    utils::{output, parse_key_val, parse_time, to_metadata, to_tags, TimeFormat},
 };
 use azure_core::request_options::{IfModifiedSinceCondition, IfTags, LeaseId};
 use azure_storage_blobs::prelude::{
@@ -17,15 +34,17 @@ use azure_storage_blobs::prelude::{
     BlobContentType, BlobVersioning, DeleteSnapshotsMethod, RehydratePriority, Snapshot, VersionId,
 };
 use clap::Subcommand;
-use std::path::PathBuf;
-use tokio::fs::read;
 use tracing::debug;
-use uuid::Uuid;
 
 #[derive(Subcommand)]
 pub enum BlobSubCommands {
     /// Get the contents of a blob
     Get(Get),
+    /// Download a "page blob", only fetching the page ranges that contain data
+    GetPageBlob(GetPageBlob),
+    /// Output the end of a blob, optionally following new content as it is appended
+    Tail(Tail),
+    LeaseExec(LeaseExec),
     /// Get properties of a blob
     GetProperties {
         // #[clap(long)]
@@ -40,6 +59,8 @@ pub enum BlobSubCommands {
         lease_id: Option<LeaseId>,
         #[clap(long)]
         if_tags: Option<IfTags>,
+        #[clap(flatten)]
+        encryption: CustomerProvidedKey,
     },
     /// Delete a blob
     Delete {
@@ -82,21 +103,7 @@ pub enum BlobSubCommands {
         metadata: Option<Vec<(String, String)>>,
     },
     /// Append the contents of the specified file to an existing "append blob" blob.
-    AppendBlock {
-        path: PathBuf,
-        #[clap(long)]
-        condition_max_size: Option<u64>,
-        #[clap(long)]
-        condition_append_position: Option<u64>,
-        #[clap(long)]
-        if_tags: Option<IfTags>,
-        #[clap(long)]
-        lease_id: Option<Uuid>,
-        // #[clap(long)]
-        // if_modified_since: Option<IfModifiedSinceCondition>,
-        // #[clap(long)]
-        // if_match: Option<IfMatchCondition>,
-    },
+    AppendBlock(AppendBlock),
     /// Create a "block blob" with the contents of the specified file.
     CreateBlockBlob(CreateBlockBlob),
     /// Create a "page blob" with the contents of the specified file.
@@ -132,6 +139,8 @@ pub enum BlobSubCommands {
         lease_id: Option<LeaseId>,
         #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
         metadata: Option<Vec<(String, String)>>,
+        #[clap(flatten)]
+        encryption: Encryption,
     },
     /// Set the access tier on the blob
     SetBlobTier {
@@ -146,22 +155,52 @@ pub enum BlobSubCommands {
         #[clap(long)]
         version_id: Option<VersionId>,
     },
+    /// Set a time-based immutability policy on the blob
+    SetImmutabilityPolicy {
+        /// Time until which the blob cannot be modified or deleted
+        until: String,
+        /// Format used for the until time
+        #[clap(long, value_enum, default_value_t = TimeFormat::Offset)]
+        time_format: TimeFormat,
+        #[clap(long, value_enum, default_value_t = ImmutabilityPolicyMode::Unlocked)]
+        mode: ImmutabilityPolicyMode,
+    },
+    /// Delete the unlocked immutability policy on the blob
+    DeleteImmutabilityPolicy,
+    /// Place a legal hold on the blob
+    SetLegalHold,
+    /// Clear the legal hold on the blob
+    ClearLegalHold,
 }
 
 #[allow(clippy::too_many_lines)]
 pub async fn blob_commands(
     blob_client: &BlobClient,
+    rest_client: &RestClient,
     subcommand: BlobSubCommands,
 ) -> azure_core::Result<()> {
     match subcommand {
         BlobSubCommands::Get(get) => {
             get.execute(blob_client).await?;
         }
-        BlobSubCommands::GetProperties { lease_id, if_tags } => {
-            let mut builder = blob_client.get_properties();
-            args!(builder, lease_id, if_tags);
-            let response = builder.await?;
-            println!("{response:#?}");
+        BlobSubCommands::GetPageBlob(get) => {
+            get.execute(blob_client, rest_client).await?;
+        }
+        BlobSubCommands::Tail(tail) => {
+            tail.execute(blob_client).await?;
+        }
+        BlobSubCommands::LeaseExec(lease_exec) => {
+            lease_exec.execute(LeaseTarget::Blob(blob_client)).await?;
+        }
+        BlobSubCommands::GetProperties {
+            lease_id,
+            if_tags,
+            encryption,
+        } => {
+            let mut headers = encryption.headers()?;
+            headers.add(lease_id);
+            headers.add(if_tags);
+            output(&get_blob_properties(blob_client, rest_client, headers).await?)?;
         }
         BlobSubCommands::Delete {
             lease_id,
@@ -196,24 +235,8 @@ pub async fn blob_commands(
             let response = builder.await?;
             debug!("{response:#?}");
         }
-        BlobSubCommands::AppendBlock {
-            path,
-            condition_max_size,
-            condition_append_position,
-            if_tags,
-            lease_id,
-        } => {
-            let bytes = read(path).await?;
-            let mut builder = blob_client.append_block(bytes);
-            args!(
-                builder,
-                condition_max_size,
-                condition_append_position,
-                if_tags,
-                lease_id
-            );
-            let response = builder.await?;
-            debug!("{response:#?}");
+        BlobSubCommands::AppendBlock(append) => {
+            append.execute(blob_client).await?;
         }
         BlobSubCommands::CreateBlockBlob(create) => {
             create.execute(blob_client).await?;
@@ -256,6 +279,7 @@ pub async fn blob_commands(
             if_tags,
             lease_id,
             metadata,
+            encryption,
         } => {
             let modified_since = modified_since
                 .map(|s| parse_time(&s, time_format))
@@ -268,7 +292,7 @@ pub async fn blob_commands(
                 .or_else(|| unmodified_since.map(IfModifiedSinceCondition::Unmodified));
             let metadata = metadata.map(to_metadata);
 
-            let mut builder = blob_client.snapshot();
+            let mut builder = blob_client.snapshot().context(encryption.context()?);
             args!(builder, if_tags, if_modified_since, lease_id, metadata);
             let response = builder.await?;
             println!("{response:#?}");
@@ -311,6 +335,24 @@ pub async fn blob_commands(
             let response = builder.await?;
             println!("{response:#?}");
         }
+        BlobSubCommands::SetImmutabilityPolicy {
+            until,
+            time_format,
+            mode,
+        } => {
+            let until = parse_time(&until, time_format)?;
+            let state = set_immutability_policy(blob_client, rest_client, until, mode).await?;
+            output(&state)?;
+        }
+        BlobSubCommands::DeleteImmutabilityPolicy => {
+            delete_immutability_policy(blob_client, rest_client).await?;
+        }
+        BlobSubCommands::SetLegalHold => {
+            output(&set_legal_hold(blob_client, rest_client, true).await?)?;
+        }
+        BlobSubCommands::ClearLegalHold => {
+            output(&set_legal_hold(blob_client, rest_client, false).await?)?;
+        }
     }
     Ok(())
 }
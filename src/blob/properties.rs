@@ -0,0 +1,138 @@
+use crate::{blob::immutability::ImmutabilityState, rest::RestClient};
+use azure_core::{
+    date,
+    error::Result,
+    headers::{
+        HeaderName, Headers, BLOB_ACCESS_TIER, BLOB_SEQUENCE_NUMBER, BLOB_TYPE, CACHE_CONTROL,
+        CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_MD5,
+        CONTENT_TYPE, COPY_COMPLETION_TIME, COPY_PROGRESS, COPY_SOURCE, COPY_STATUS, CREATION_TIME,
+        ETAG, LAST_MODIFIED, LEASE_DURATION, LEASE_STATE, LEASE_STATUS, SERVER_ENCRYPTED,
+    },
+    Method,
+};
+use azure_storage::clients::ServiceType;
+use azure_storage_blobs::prelude::BlobClient;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+const ACCESS_TIER_INFERRED: HeaderName = HeaderName::from_static("x-ms-access-tier-inferred");
+const ACCESS_TIER_CHANGE_TIME: HeaderName = HeaderName::from_static("x-ms-access-tier-change-time");
+const ARCHIVE_STATUS: HeaderName = HeaderName::from_static("x-ms-archive-status");
+const COMMITTED_BLOCK_COUNT: HeaderName =
+    HeaderName::from_static("x-ms-blob-committed-block-count");
+const ENCRYPTION_SCOPE: HeaderName = HeaderName::from_static("x-ms-encryption-scope");
+const VERSION_ID: HeaderName = HeaderName::from_static("x-ms-version-id");
+const IS_CURRENT_VERSION: HeaderName = HeaderName::from_static("x-ms-is-current-version");
+const TAG_COUNT: HeaderName = HeaderName::from_static("x-ms-tag-count");
+const META_PREFIX: &str = "x-ms-meta-";
+
+/// Properties of a blob
+///
+/// The SDK's blob properties omit the immutability state and cannot be
+/// serialized, so these are read from the REST API directly.
+#[derive(Debug, Serialize)]
+pub(crate) struct BlobProperties {
+    pub blob_type: Option<String>,
+    pub content_length: Option<u64>,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_md5: Option<String>,
+    pub cache_control: Option<String>,
+    pub etag: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_modified: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub creation_time: Option<OffsetDateTime>,
+    pub access_tier: Option<String>,
+    pub access_tier_inferred: Option<bool>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub access_tier_change_time: Option<OffsetDateTime>,
+    pub archive_status: Option<String>,
+    pub sequence_number: Option<u64>,
+    pub committed_block_count: Option<u64>,
+    pub lease_status: Option<String>,
+    pub lease_state: Option<String>,
+    pub lease_duration: Option<String>,
+    pub server_encrypted: Option<bool>,
+    pub encryption_scope: Option<String>,
+    pub version_id: Option<String>,
+    pub is_current_version: Option<bool>,
+    pub tag_count: Option<u64>,
+    pub copy_status: Option<String>,
+    pub copy_source: Option<String>,
+    pub copy_progress: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub copy_completion_time: Option<OffsetDateTime>,
+    #[serde(flatten)]
+    pub immutability: ImmutabilityState,
+    pub metadata: BTreeMap<String, String>,
+}
+
+fn optional_date(headers: &Headers, name: &HeaderName) -> Result<Option<OffsetDateTime>> {
+    headers
+        .get_optional_str(name)
+        .map(date::parse_rfc1123)
+        .transpose()
+}
+
+/// Read the properties of a blob
+///
+/// `headers` carry the request conditions, such as the lease id and the
+/// customer-provided encryption key.
+pub(crate) async fn get_blob_properties(
+    blob_client: &BlobClient,
+    rest_client: &RestClient,
+    headers: Headers,
+) -> Result<BlobProperties> {
+    let url = blob_client.url()?;
+    let response = rest_client
+        .send(ServiceType::Blob, Method::Head, url, headers, None)
+        .await?;
+    let headers = response.headers();
+
+    let metadata = headers
+        .iter()
+        .filter_map(|(name, value)| {
+            name.as_str()
+                .strip_prefix(META_PREFIX)
+                .map(|name| (name.to_string(), value.as_str().to_string()))
+        })
+        .collect();
+
+    Ok(BlobProperties {
+        blob_type: headers.get_optional_string(&BLOB_TYPE),
+        content_length: headers.get_optional_as(&CONTENT_LENGTH)?,
+        content_type: headers.get_optional_string(&CONTENT_TYPE),
+        content_encoding: headers.get_optional_string(&CONTENT_ENCODING),
+        content_language: headers.get_optional_string(&CONTENT_LANGUAGE),
+        content_disposition: headers.get_optional_string(&CONTENT_DISPOSITION),
+        content_md5: headers.get_optional_string(&CONTENT_MD5),
+        cache_control: headers.get_optional_string(&CACHE_CONTROL),
+        etag: headers.get_optional_string(&ETAG),
+        last_modified: optional_date(headers, &LAST_MODIFIED)?,
+        creation_time: optional_date(headers, &CREATION_TIME)?,
+        access_tier: headers.get_optional_string(&BLOB_ACCESS_TIER),
+        access_tier_inferred: headers.get_optional_as(&ACCESS_TIER_INFERRED)?,
+        access_tier_change_time: optional_date(headers, &ACCESS_TIER_CHANGE_TIME)?,
+        archive_status: headers.get_optional_string(&ARCHIVE_STATUS),
+        sequence_number: headers.get_optional_as(&BLOB_SEQUENCE_NUMBER)?,
+        committed_block_count: headers.get_optional_as(&COMMITTED_BLOCK_COUNT)?,
+        lease_status: headers.get_optional_string(&LEASE_STATUS),
+        lease_state: headers.get_optional_string(&LEASE_STATE),
+        lease_duration: headers.get_optional_string(&LEASE_DURATION),
+        server_encrypted: headers.get_optional_as(&SERVER_ENCRYPTED)?,
+        encryption_scope: headers.get_optional_string(&ENCRYPTION_SCOPE),
+        version_id: headers.get_optional_string(&VERSION_ID),
+        is_current_version: headers.get_optional_as(&IS_CURRENT_VERSION)?,
+        tag_count: headers.get_optional_as(&TAG_COUNT)?,
+        copy_status: headers.get_optional_string(&COPY_STATUS),
+        copy_source: headers.get_optional_string(&COPY_SOURCE),
+        copy_progress: headers.get_optional_string(&COPY_PROGRESS),
+        copy_completion_time: optional_date(headers, &COPY_COMPLETION_TIME)?,
+        immutability: ImmutabilityState::from_headers(headers)?,
+        metadata,
+    })
+}
@@ -0,0 +1,206 @@
+use crate::{
+    args,
+    utils::{http_status, parse_duration},
+};
+use azure_core::{
+    error::Result,
+    prelude::Range,
+    request_options::{IfMatchCondition, LeaseId},
+    StatusCode,
+};
+use azure_storage_blobs::{blob::BlobProperties, prelude::BlobClient};
+use futures::StreamExt;
+use tokio::{
+    io::{stdout, AsyncWriteExt},
+    time::sleep,
+};
+use tracing::info;
+
+/// How much to read at a time while searching backwards for the last lines
+const LINE_SEARCH_CHUNK_SIZE: u64 = 64 * 1024;
+/// How far back from the end to search for the last lines, after which the
+/// output starts partway through a line
+const MAX_LINE_SEARCH_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Output the end of a blob, optionally following new content as it is appended
+#[derive(clap::Parser)]
+pub struct Tail {
+    /// Keep polling the blob and output new content as it is appended
+    #[clap(long)]
+    follow: bool,
+    /// Number of lines from the end of the blob to output
+    #[clap(long, default_value_t = 10)]
+    lines: usize,
+    /// How often to check the blob for new content when following
+    ///
+    /// Duration as parsed by <https://docs.rs/duration-string/latest/duration_string/>
+    /// Examples include `500ms`, `1s`, and `1m`
+    #[clap(long, default_value = "1s")]
+    interval: String,
+    #[clap(long)]
+    lease_id: Option<LeaseId>,
+}
+
+impl Tail {
+    pub async fn execute(self, blob_client: &BlobClient) -> Result<()> {
+        let Tail {
+            follow,
+            lines,
+            interval,
+            lease_id,
+        } = self;
+        let interval = parse_duration(&interval)?;
+
+        let (initial, buf) = read_end(blob_client, lease_id, lines).await?;
+
+        let mut handle = stdout();
+        let mut offset = initial.content_length;
+        handle.write_all(last_lines(&buf, lines)).await?;
+        handle.flush().await?;
+
+        if !follow {
+            return Ok(());
+        }
+
+        loop {
+            sleep(interval).await;
+
+            let mut builder = blob_client.get_properties();
+            args!(builder, lease_id);
+            let current = match builder.await {
+                Ok(response) => response.blob.properties,
+                Err(err) if http_status(&err) == Some(StatusCode::NotFound) => {
+                    info!("blob was deleted");
+                    return Ok(());
+                }
+                Err(err) => return Err(err),
+            };
+
+            if current.blob_type != initial.blob_type
+                || current.creation_time != initial.creation_time
+                || current.content_length < offset
+            {
+                info!("blob was replaced");
+                return Ok(());
+            }
+
+            if current.content_length == offset {
+                continue;
+            }
+
+            // The content is requested for the ETag the length was read from.
+            // If the blob changed in between, try again on the next poll.
+            let data = match get_range(
+                blob_client,
+                &current,
+                lease_id,
+                offset..current.content_length,
+            )
+            .await
+            {
+                Ok(data) => data,
+                Err(err) => match http_status(&err) {
+                    Some(StatusCode::PreconditionFailed) => continue,
+                    Some(StatusCode::NotFound) => {
+                        info!("blob was deleted");
+                        return Ok(());
+                    }
+                    _ => return Err(err),
+                },
+            };
+            handle.write_all(&data).await?;
+            handle.flush().await?;
+            offset += data.len() as u64;
+        }
+    }
+}
+
+/// Read backwards from the end of the blob until the content includes the
+/// requested number of lines
+///
+/// The chunks are requested for the etag of the properties they are read
+/// with.  If the blob is appended to in between, the search starts again from
+/// the new end of the blob.  The search stops after `MAX_LINE_SEARCH_SIZE`
+/// bytes, so a blob without line breaks is not read into memory in full.
+async fn read_end(
+    blob_client: &BlobClient,
+    lease_id: Option<LeaseId>,
+    lines: usize,
+) -> Result<(BlobProperties, Vec<u8>)> {
+    'search: loop {
+        let mut builder = blob_client.get_properties();
+        args!(builder, lease_id);
+        let properties = builder.await?.blob.properties;
+
+        let mut start = properties.content_length;
+        let mut buf = vec![];
+        let search_start = properties
+            .content_length
+            .saturating_sub(MAX_LINE_SEARCH_SIZE);
+        while start > search_start && count_lines(&buf) < lines {
+            let chunk_start = start
+                .saturating_sub(LINE_SEARCH_CHUNK_SIZE)
+                .max(search_start);
+            let mut chunk =
+                match get_range(blob_client, &properties, lease_id, chunk_start..start).await {
+                    Ok(chunk) => chunk,
+                    Err(err) if http_status(&err) == Some(StatusCode::PreconditionFailed) => {
+                        continue 'search;
+                    }
+                    Err(err) => return Err(err),
+                };
+            chunk.extend_from_slice(&buf);
+            buf = chunk;
+            start = chunk_start;
+        }
+        if start > 0 && count_lines(&buf) < lines {
+            info!(
+                "fewer than {lines} lines in the last {MAX_LINE_SEARCH_SIZE} bytes, starting partway through a line"
+            );
+        }
+        return Ok((properties, buf));
+    }
+}
+
+async fn get_range(
+    blob_client: &BlobClient,
+    properties: &BlobProperties,
+    lease_id: Option<LeaseId>,
+    range: std::ops::Range<u64>,
+) -> Result<Vec<u8>> {
+    let mut builder = blob_client
+        .get()
+        .range(Range::from(range))
+        .if_match(IfMatchCondition::Match(properties.etag.to_string()));
+    args!(builder, lease_id);
+
+    let mut data = vec![];
+    let mut stream = builder.into_stream();
+    while let Some(response) = stream.next().await {
+        let mut body = response?.data;
+        while let Some(chunk) = body.next().await {
+            data.extend_from_slice(&chunk?);
+        }
+    }
+    Ok(data)
+}
+
+/// Count the complete lines, ignoring a trailing newline at the end of the content
+fn count_lines(buf: &[u8]) -> usize {
+    let body = buf.strip_suffix(b"\n").unwrap_or(buf);
+    body.split(|x| *x == b'\n').count() - 1
+}
+
+fn last_lines(buf: &[u8], lines: usize) -> &[u8] {
+    if lines == 0 {
+        return &[];
+    }
+    let body = buf.strip_suffix(b"\n").unwrap_or(buf);
+    body.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, x)| **x == b'\n')
+        .nth(lines - 1)
+        .and_then(|(i, _)| buf.get(i + 1..))
+        .unwrap_or(buf)
+}
@@ -0,0 +1,419 @@
+//! Blob Batch API, which sends up to 256 sub-requests in a single
+//! `multipart/mixed` request.
+//!
+//! See <https://learn.microsoft.com/rest/api/storageservices/blob-batch>
+
+use crate::{rest::RestClient, utils::output};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    headers::{Headers, CONTENT_TYPE},
+    Method, StatusCode, Url,
+};
+use azure_storage::clients::ServiceType;
+use azure_storage_blobs::prelude::{
+    AccessTier, ContainerClient, DeleteSnapshotsMethod, RehydratePriority,
+};
+use clap::Args;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::{fmt::Write as _, path::PathBuf, time::Duration};
+use tokio::{
+    fs::File,
+    io::{stdin, AsyncBufReadExt, AsyncRead, BufReader},
+    time::sleep,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// The most sub-requests the service accepts in a single batch
+const MAX_BATCH_SIZE: usize = 256;
+/// How many times each sub-request is attempted before it is reported as failed
+const MAX_ATTEMPTS: u64 = 3;
+const RETRY_STATUSES: &[u16] = &[408, 429, 500, 502, 503, 504];
+
+/// Blobs to include in a batch operation
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub(crate) struct BatchBlobs {
+    /// File with one blob name per line (use `-` to read from STDIN)
+    #[clap(long)]
+    names_file: Option<PathBuf>,
+    /// Use each blob with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+}
+
+impl BatchBlobs {
+    async fn names(&self, container_client: &ContainerClient) -> Result<Vec<String>> {
+        if let Some(path) = &self.names_file {
+            let handle: Box<dyn AsyncRead + Unpin> = if path.as_os_str() == "-" {
+                Box::new(stdin())
+            } else {
+                Box::new(File::open(path).await?)
+            };
+            let mut lines = BufReader::new(handle).lines();
+            let mut names = vec![];
+            while let Some(line) = lines.next_line().await? {
+                let line = line.trim_end_matches('\r');
+                if !line.is_empty() {
+                    names.push(line.to_string());
+                }
+            }
+            return Ok(names);
+        }
+
+        let mut builder = container_client.list_blobs();
+        if let Some(prefix) = &self.prefix {
+            builder = builder.prefix(prefix.clone());
+        }
+        let mut names = vec![];
+        let mut stream = builder.into_stream();
+        while let Some(response) = stream.next().await {
+            names.extend(response?.blobs.blobs().map(|blob| blob.name.clone()));
+        }
+        Ok(names)
+    }
+}
+
+/// Delete blobs using the Blob Batch API
+#[derive(clap::Parser)]
+pub struct BatchDelete {
+    #[clap(flatten)]
+    blobs: BatchBlobs,
+    /// How to handle snapshots of the blobs (defaults to deleting them with the blob)
+    #[clap(long)]
+    delete_snapshots_method: Option<DeleteSnapshotsMethod>,
+    /// Number of batches to send at a time
+    #[clap(long, default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+}
+
+impl BatchDelete {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let mut headers = Headers::new();
+        headers.add(
+            self.delete_snapshots_method
+                .unwrap_or(DeleteSnapshotsMethod::Include),
+        );
+        let operation = SubRequest {
+            method: Method::Delete,
+            comp: None,
+            headers,
+        };
+        let names = self.blobs.names(container_client).await?;
+        run(
+            container_client,
+            rest_client,
+            &operation,
+            names,
+            self.concurrency,
+        )
+        .await
+    }
+}
+
+/// Set the access tier of blobs using the Blob Batch API
+#[derive(clap::Parser)]
+pub struct BatchSetTier {
+    #[clap(flatten)]
+    blobs: BatchBlobs,
+    #[clap(long)]
+    tier: AccessTier,
+    #[clap(long)]
+    rehydrate_priority: Option<RehydratePriority>,
+    /// Number of batches to send at a time
+    #[clap(long, default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+}
+
+impl BatchSetTier {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let mut headers = Headers::new();
+        headers.add(self.tier);
+        headers.add(self.rehydrate_priority);
+        let operation = SubRequest {
+            method: Method::Put,
+            comp: Some("tier"),
+            headers,
+        };
+        let names = self.blobs.names(container_client).await?;
+        run(
+            container_client,
+            rest_client,
+            &operation,
+            names,
+            self.concurrency,
+        )
+        .await
+    }
+}
+
+/// The operation applied to each blob in the batch
+struct SubRequest {
+    method: Method,
+    comp: Option<&'static str>,
+    headers: Headers,
+}
+
+impl SubRequest {
+    fn url(&self, container_client: &ContainerClient, name: &str) -> Result<Url> {
+        let mut url = container_client.blob_client(name).url()?;
+        if let Some(comp) = self.comp {
+            url.query_pairs_mut().append_pair("comp", comp);
+        }
+        Ok(url)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Failure {
+    name: String,
+    status: Option<u16>,
+    error_code: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    succeeded: usize,
+    failed: Vec<Failure>,
+}
+
+/// Result of a single sub-request
+#[derive(Clone)]
+struct Outcome {
+    status: Option<u16>,
+    error_code: Option<String>,
+}
+
+impl Outcome {
+    fn is_success(&self) -> bool {
+        self.status
+            .is_some_and(|status| (200..300).contains(&status))
+    }
+
+    /// A sub-request without a response is retried, as the service did not
+    /// report processing it
+    fn is_retriable(&self) -> bool {
+        self.status
+            .is_none_or(|status| RETRY_STATUSES.contains(&status))
+    }
+}
+
+async fn run(
+    container_client: &ContainerClient,
+    rest_client: &RestClient,
+    operation: &SubRequest,
+    mut pending: Vec<String>,
+    concurrency: usize,
+) -> Result<()> {
+    let mut summary = Summary {
+        succeeded: 0,
+        failed: vec![],
+    };
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        if pending.is_empty() {
+            break;
+        }
+        if attempt > 1 {
+            sleep(Duration::from_secs(attempt)).await;
+        }
+
+        let results = stream::iter(pending.chunks(MAX_BATCH_SIZE))
+            .map(|names| async move {
+                match send_batch(container_client, rest_client, operation, names).await {
+                    Ok(outcomes) => outcomes,
+                    Err(err) => batch_failed(names, &err),
+                }
+            })
+            .buffer_unordered(concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut retry = vec![];
+        for outcomes in results {
+            for (name, outcome) in outcomes {
+                if outcome.is_success() {
+                    summary.succeeded += 1;
+                } else if outcome.is_retriable() && attempt < MAX_ATTEMPTS {
+                    retry.push(name);
+                } else {
+                    summary.failed.push(Failure {
+                        name,
+                        status: outcome.status,
+                        error_code: outcome.error_code,
+                    });
+                }
+            }
+        }
+        pending = retry;
+    }
+
+    output(&summary)?;
+    if !summary.failed.is_empty() {
+        return Err(Error::with_message(ErrorKind::Other, || {
+            format!("{} sub-requests failed", summary.failed.len())
+        }));
+    }
+    Ok(())
+}
+
+/// Record the failure of a whole batch as the outcome of each of its
+/// sub-requests, so the rest of the run continues
+fn batch_failed(names: &[String], err: &Error) -> Vec<(String, Outcome)> {
+    error!("batch of {} sub-requests failed: {err}", names.len());
+    let http_error = err.as_http_error();
+    names
+        .iter()
+        .map(|name| {
+            let outcome = Outcome {
+                status: http_error.map(|err| u16::from(err.status())),
+                error_code: http_error
+                    .and_then(|err| err.error_code())
+                    .map(ToString::to_string),
+            };
+            (name.clone(), outcome)
+        })
+        .collect()
+}
+
+async fn send_batch(
+    container_client: &ContainerClient,
+    rest_client: &RestClient,
+    operation: &SubRequest,
+    names: &[String],
+) -> Result<Vec<(String, Outcome)>> {
+    let boundary = format!("batch_{}", Uuid::new_v4());
+    let mut body = String::new();
+    for (content_id, name) in names.iter().enumerate() {
+        let url = operation.url(container_client, name)?;
+        let request = rest_client
+            .sign(
+                ServiceType::Blob,
+                operation.method,
+                url,
+                operation.headers.clone(),
+            )
+            .await?;
+
+        let _ = write!(
+            body,
+            "--{boundary}\r\n\
+             Content-Type: application/http\r\n\
+             Content-Transfer-Encoding: binary\r\n\
+             Content-ID: {content_id}\r\n\
+             \r\n\
+             {} {} HTTP/1.1\r\n",
+            request.method().as_ref(),
+            request.path_and_query()
+        );
+        for (key, value) in request.headers().iter() {
+            let _ = write!(body, "{}: {}\r\n", key.as_str(), value.as_str());
+        }
+        body.push_str("\r\n");
+    }
+    let _ = write!(body, "--{boundary}--\r\n");
+
+    let mut url = container_client.url()?;
+    url.query_pairs_mut()
+        .append_pair("restype", "container")
+        .append_pair("comp", "batch");
+    let mut headers = Headers::new();
+    headers.insert(
+        CONTENT_TYPE,
+        format!("multipart/mixed; boundary={boundary}"),
+    );
+
+    let response = rest_client
+        .send(
+            ServiceType::Blob,
+            Method::Post,
+            url,
+            headers,
+            Some(body.into()),
+        )
+        .await?;
+    if response.status() != StatusCode::Accepted {
+        return Err(Error::with_message(ErrorKind::Other, || {
+            format!("unexpected batch response status: {}", response.status())
+        }));
+    }
+    let content_type = response.headers().get_str(&CONTENT_TYPE)?.to_string();
+    let response_boundary = content_type
+        .split(';')
+        .find_map(|param| param.trim().strip_prefix("boundary="))
+        .ok_or_else(|| {
+            Error::with_message(ErrorKind::DataConversion, || {
+                format!("missing boundary in batch response content type: {content_type}")
+            })
+        })?
+        .trim_matches('"')
+        .to_string();
+    let body = response.into_body().collect().await?;
+
+    let outcomes = parse_response(
+        &String::from_utf8_lossy(&body),
+        &response_boundary,
+        names.len(),
+    );
+    Ok(names
+        .iter()
+        .cloned()
+        .zip(outcomes)
+        .map(|(name, outcome)| {
+            let outcome = outcome.unwrap_or(Outcome {
+                status: None,
+                error_code: None,
+            });
+            (name, outcome)
+        })
+        .collect())
+}
+
+/// Parse the sub-responses, indexed by the Content-ID of their sub-request.
+/// Sub-responses without a Content-ID are assumed to be in request order, and
+/// those with a Content-ID outside the `count` sub-requests are ignored.
+fn parse_response(body: &str, boundary: &str, count: usize) -> Vec<Option<Outcome>> {
+    let mut outcomes = vec![None; count];
+    let delimiter = format!("--{boundary}");
+    for (position, part) in body.split(delimiter.as_str()).skip(1).enumerate() {
+        if part.starts_with("--") {
+            break;
+        }
+        let part = part.trim_start_matches("\r\n");
+        let Some((part_headers, http)) = part.split_once("\r\n\r\n") else {
+            continue;
+        };
+
+        let content_id = part_headers
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("content-id"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(position);
+
+        let mut lines = http.lines();
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok());
+        let error_code = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("x-ms-error-code"))
+            .map(|(_, value)| value.trim().to_string());
+
+        if let Some(outcome) = outcomes.get_mut(content_id) {
+            *outcome = Some(Outcome { status, error_code });
+        }
+    }
+    outcomes
+}
@@ -0,0 +1,197 @@
+use crate::{
+    find_blobs_by_tags::{find_blobs_by_tags, TagFilter},
+    rest::RestClient,
+    utils::output,
+};
+use azure_core::error::{Error, ErrorKind, Result};
+use azure_storage_blobs::{
+    blob::Blob,
+    container::operations::ListBlobsBuilder,
+    prelude::{BlobClient, ContainerClient},
+};
+use clap::Args;
+use futures::{stream, Future, StreamExt};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashSet;
+use tracing::error;
+
+/// Characters that start a wildcard in a glob pattern
+const GLOB_SPECIAL: &[char] = &['*', '?', '['];
+
+/// Select the blobs in a container that an operation applies to
+#[derive(Debug, Args)]
+#[group(required = true, multiple = true)]
+pub(crate) struct BlobFilter {
+    /// only include blobs with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// only include blobs with names matching the glob pattern
+    ///
+    /// `*` and `?` do not match `/`, while `**` matches any number of path segments
+    #[clap(long)]
+    glob: Option<Pattern>,
+    /// only include blobs with tags matching the filter expression
+    ///
+    /// For example: `"project" = 'alpha' AND "priority" >= '2'`
+    #[clap(long)]
+    tag_filter: Option<TagFilter>,
+}
+
+impl BlobFilter {
+    fn list_prefix(&self) -> Option<String> {
+        list_prefix(self.prefix.as_deref(), self.glob.as_ref())
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        matches(self.prefix.as_deref(), self.glob.as_ref(), name)
+    }
+
+    /// Names of the blobs in the container that match the tag filter, if
+    /// one was specified
+    async fn tagged_names(
+        &self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<Option<HashSet<String>>> {
+        let Some(tag_filter) = &self.tag_filter else {
+            return Ok(None);
+        };
+        if tag_filter.has_container_condition() {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "@container is only supported when searching the account",
+            ));
+        }
+        let mut url = container_client.url()?;
+        url.query_pairs_mut().append_pair("restype", "container");
+        let blobs = find_blobs_by_tags(rest_client, url, tag_filter, None).await?;
+        Ok(Some(blobs.into_iter().map(|blob| blob.name).collect()))
+    }
+
+    /// Names of the blobs in the container that match the filter
+    pub(crate) async fn blob_names(
+        &self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<Vec<String>> {
+        if let Some(tagged) = self.tagged_names(container_client, rest_client).await? {
+            let mut names: Vec<String> = tagged
+                .into_iter()
+                .filter(|name| self.matches(name))
+                .collect();
+            names.sort();
+            return Ok(names);
+        }
+
+        let blobs = self
+            .blobs(container_client.list_blobs(), container_client, rest_client)
+            .await?;
+        Ok(blobs.into_iter().map(|blob| blob.name).collect())
+    }
+
+    /// Entries of the listing that match the filter
+    ///
+    /// The listing can include snapshots and versions, which match along with
+    /// their blob.
+    pub(crate) async fn blobs(
+        &self,
+        mut builder: ListBlobsBuilder,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<Vec<Blob>> {
+        let tagged = self.tagged_names(container_client, rest_client).await?;
+        if let Some(prefix) = self.list_prefix() {
+            builder = builder.prefix(prefix);
+        }
+        let mut blobs = vec![];
+        let mut stream = builder.into_stream();
+        while let Some(response) = stream.next().await {
+            for blob in response?.blobs.blobs() {
+                if self.matches(&blob.name)
+                    && tagged
+                        .as_ref()
+                        .is_none_or(|tagged| tagged.contains(&blob.name))
+                {
+                    blobs.push(blob.clone());
+                }
+            }
+        }
+        Ok(blobs)
+    }
+}
+
+/// The prefix used to list the container, which narrows the listing to the
+/// literal portion of the glob pattern when no prefix is specified.
+pub(crate) fn list_prefix(prefix: Option<&str>, glob: Option<&Pattern>) -> Option<String> {
+    if let Some(prefix) = prefix {
+        return Some(prefix.to_string());
+    }
+    let pattern = glob?.as_str();
+    let literal = &pattern[..pattern.find(GLOB_SPECIAL).unwrap_or(pattern.len())];
+    (!literal.is_empty()).then(|| literal.to_string())
+}
+
+/// Check the blob name against the prefix and glob pattern
+pub(crate) fn matches(prefix: Option<&str>, glob: Option<&Pattern>, name: &str) -> bool {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::default()
+    };
+    prefix.is_none_or(|prefix| name.starts_with(prefix))
+        && glob.is_none_or(|glob| glob.matches_with(name, options))
+}
+
+/// Options for operations applied to many blobs
+#[derive(Debug, Args)]
+pub(crate) struct BulkOptions {
+    /// Number of blobs to update at a time
+    #[clap(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    /// Only output the names of the blobs that would be updated
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl BulkOptions {
+    /// Apply the operation to each blob matching the filter, continuing past
+    /// failures so that one bad blob does not stop the rest.
+    pub(crate) async fn for_each_blob<F, Fut>(
+        &self,
+        filter: &BlobFilter,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+        operation: F,
+    ) -> Result<()>
+    where
+        F: Fn(BlobClient) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let names = filter.blob_names(container_client, rest_client).await?;
+        if self.dry_run {
+            return output(&names);
+        }
+
+        let results = stream::iter(names)
+            .map(|name| {
+                let future = operation(container_client.blob_client(&name));
+                async move { (name, future.await) }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut failed = 0;
+        for (name, result) in results {
+            if let Err(err) = result {
+                error!("{name}: {err}");
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to update {failed} blobs")
+            }));
+        }
+        Ok(())
+    }
+}
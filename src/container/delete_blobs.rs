@@ -0,0 +1,266 @@
+use crate::{
+    container::bulk::BlobFilter,
+    rest::RestClient,
+    utils::{confirm, output, parse_duration},
+};
+use azure_core::error::{Error, ErrorKind, Result};
+use azure_storage_blobs::{
+    blob::Blob,
+    prelude::{AccessTier, ContainerClient, DeleteSnapshotsMethod, VersionId},
+};
+use clap::ValueEnum;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::collections::HashSet;
+use time::OffsetDateTime;
+use tracing::{debug, error};
+
+/// How blobs with snapshots are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WithSnapshots {
+    /// Delete the blob along with its snapshots
+    Include,
+    /// Delete only the snapshots, keeping the blob
+    Only,
+}
+
+/// Delete the blobs in the container that match the filters
+///
+/// Blobs that have snapshots are skipped unless `--with-snapshots` is specified.
+#[derive(clap::Parser)]
+pub struct DeleteBlobs {
+    #[clap(flatten)]
+    filter: BlobFilter,
+    /// only include blobs last modified longer ago than this
+    ///
+    /// Duration as parsed by <https://docs.rs/duration-string/latest/duration_string/>
+    /// Examples include `10d`, `1h`, `1h30m`, and `1h30m10s`
+    #[clap(long, group = "BlobFilter")]
+    older_than: Option<String>,
+    /// only include blobs in the specified access tier
+    #[clap(long, group = "BlobFilter")]
+    tier: Option<AccessTier>,
+    /// how to handle blobs that have snapshots
+    #[clap(long, value_enum)]
+    with_snapshots: Option<WithSnapshots>,
+    /// also delete previous versions of the blobs
+    #[clap(long)]
+    include_versions: bool,
+    /// fail without deleting anything if more than this many blobs match
+    #[clap(long)]
+    max_delete: Option<usize>,
+    /// Number of blobs to delete at a time
+    #[clap(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    /// delete without asking for confirmation
+    #[clap(long)]
+    yes: bool,
+    /// Only output the blobs that would be deleted
+    #[clap(long, conflicts_with = "yes")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Target {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_id: Option<String>,
+    #[serde(skip)]
+    delete_snapshots_method: DeleteSnapshotsMethod,
+}
+
+#[derive(Debug, Serialize)]
+struct DryRun {
+    blobs: Vec<Target>,
+    skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    deleted: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+impl DeleteBlobs {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let (targets, skipped) = self.targets(container_client, rest_client).await?;
+
+        if self.dry_run {
+            return output(&DryRun {
+                blobs: targets,
+                skipped,
+            });
+        }
+
+        if let Some(max_delete) = self.max_delete {
+            if targets.len() > max_delete {
+                return Err(Error::with_message(ErrorKind::Other, || {
+                    format!(
+                        "{} blobs match, which is more than --max-delete {max_delete}",
+                        targets.len()
+                    )
+                }));
+            }
+        }
+
+        if targets.is_empty() {
+            return output(&Summary {
+                deleted: 0,
+                skipped,
+                failed: 0,
+            });
+        }
+
+        if !self.yes
+            && !confirm(&format!(
+                "Delete {} blobs from {}?",
+                targets.len(),
+                container_client.container_name()
+            ))?
+        {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "deletion was not confirmed",
+            ));
+        }
+
+        let results = stream::iter(targets)
+            .map(|target| async move {
+                let result = delete(container_client, &target).await;
+                (target, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut summary = Summary {
+            deleted: 0,
+            skipped,
+            failed: 0,
+        };
+        for (target, result) in results {
+            match result {
+                Ok(()) => summary.deleted += 1,
+                Err(err) => {
+                    let version = target
+                        .version_id
+                        .as_deref()
+                        .map(|version_id| format!(" ({version_id})"))
+                        .unwrap_or_default();
+                    error!("{}{version}: {err}", target.name);
+                    summary.failed += 1;
+                }
+            }
+        }
+        output(&summary)?;
+
+        if summary.failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to delete {} blobs", summary.failed)
+            }));
+        }
+        Ok(())
+    }
+
+    /// List the blobs to delete, along with the number of blobs skipped
+    async fn targets(
+        &self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<(Vec<Target>, usize)> {
+        let older_than = self
+            .older_than
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?
+            .map(|duration| OffsetDateTime::now_utc() - duration);
+
+        let builder = container_client
+            .list_blobs()
+            .include_snapshots(true)
+            .include_versions(self.include_versions);
+        let blobs = self
+            .filter
+            .blobs(builder, container_client, rest_client)
+            .await?;
+
+        // Snapshots are listed as separate entries, which are only used to
+        // find the blobs that have snapshots
+        let with_snapshots: HashSet<String> = blobs
+            .iter()
+            .filter(|blob| blob.snapshot.is_some())
+            .map(|blob| blob.name.clone())
+            .collect();
+
+        let mut targets = vec![];
+        let mut skipped = 0;
+        for blob in blobs.into_iter().filter(|blob| blob.snapshot.is_none()) {
+            match self.target(blob, older_than, &with_snapshots) {
+                Some(target) => targets.push(target),
+                None => skipped += 1,
+            }
+        }
+        Ok((targets, skipped))
+    }
+
+    /// Decide how to delete the blob, if it should be deleted at all
+    fn target(
+        &self,
+        blob: Blob,
+        older_than: Option<OffsetDateTime>,
+        with_snapshots: &HashSet<String>,
+    ) -> Option<Target> {
+        if older_than.is_some_and(|older_than| blob.properties.last_modified >= older_than) {
+            return None;
+        }
+        if self
+            .tier
+            .is_some_and(|tier| blob.properties.access_tier != Some(tier))
+        {
+            return None;
+        }
+
+        // Previous versions are deleted individually and do not have snapshots
+        if blob.is_current_version == Some(false) {
+            return (self.with_snapshots != Some(WithSnapshots::Only)).then_some(Target {
+                name: blob.name,
+                version_id: blob.version_id,
+                delete_snapshots_method: DeleteSnapshotsMethod::Include,
+            });
+        }
+
+        let has_snapshots = with_snapshots.contains(&blob.name);
+        let delete_snapshots_method = match (self.with_snapshots, has_snapshots) {
+            (None, true) | (Some(WithSnapshots::Only), false) => return None,
+            (Some(WithSnapshots::Only), true) => DeleteSnapshotsMethod::Only,
+            (Some(WithSnapshots::Include) | None, _) => DeleteSnapshotsMethod::Include,
+        };
+        Some(Target {
+            name: blob.name,
+            version_id: None,
+            delete_snapshots_method,
+        })
+    }
+}
+
+async fn delete(container_client: &ContainerClient, target: &Target) -> Result<()> {
+    let blob_client = container_client.blob_client(&target.name);
+    if let Some(version_id) = &target.version_id {
+        let response = blob_client
+            .delete_version_id(VersionId::new(version_id.clone()))
+            .await?;
+        debug!("{response:#?}");
+    } else {
+        let response = blob_client
+            .delete()
+            .delete_snapshots_method(target.delete_snapshots_method)
+            .await?;
+        debug!("{response:#?}");
+    }
+    Ok(())
+}
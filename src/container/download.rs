@@ -0,0 +1,289 @@
+use crate::{
+    blob::get::{DownloadOptions, GetConditions},
+    container::{
+        bulk::{list_prefix, matches},
+        local::{local_path, set_modified},
+    },
+    utils::{file_md5, output},
+};
+use azure_core::error::{Error, ErrorKind, Result};
+use azure_storage_blobs::{
+    blob::Blob,
+    prelude::{BlobVersioning, ContainerClient, VersionId},
+};
+use futures::{stream, StreamExt};
+use glob::Pattern;
+use serde::Serialize;
+use std::{collections::HashSet, fs, path::PathBuf, time::Instant};
+use time::OffsetDateTime;
+use tokio::fs::{create_dir_all, File};
+use tracing::{debug, error, warn};
+
+/// Download the blobs in a container to a local directory
+#[derive(clap::Parser)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Download {
+    /// local directory to download to
+    local_dir: PathBuf,
+    /// only include blobs with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// only include blobs with names matching the glob pattern
+    ///
+    /// `*` and `?` do not match `/`, while `**` matches any number of path segments
+    #[clap(long)]
+    glob: Option<Pattern>,
+    /// write every file directly in the local directory, using the last
+    /// segment of the blob name
+    #[clap(long)]
+    flatten: bool,
+    /// skip files that already exist with the size and `Content-MD5` of the blob
+    #[clap(long)]
+    skip_existing: bool,
+    /// also download snapshots, to files with a `.snapshot-<SNAPSHOT>` suffix
+    #[clap(long)]
+    include_snapshots: bool,
+    /// also download previous versions, to files with a `.version-<VERSION_ID>` suffix
+    #[clap(long)]
+    include_versions: bool,
+    /// Number of blobs to download at a time
+    #[clap(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    #[clap(flatten)]
+    options: DownloadOptions,
+}
+
+/// A blob, snapshot or version to download
+#[derive(Debug)]
+struct Target {
+    name: String,
+    blob_versioning: Option<BlobVersioning>,
+    path: PathBuf,
+    size: u64,
+    last_modified: OffsetDateTime,
+    content_md5: Option<[u8; 16]>,
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Downloaded(u64),
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    downloaded: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    seconds: f64,
+    bytes_per_second: f64,
+}
+
+impl Download {
+    pub async fn execute(self, container_client: &ContainerClient) -> Result<()> {
+        let start = Instant::now();
+        let (targets, skipped) = self.targets(container_client).await?;
+
+        let this = &self;
+        let results = stream::iter(targets)
+            .map(|target| async move {
+                let result = this.download(container_client, &target).await;
+                (target, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut summary = Summary {
+            downloaded: 0,
+            skipped,
+            failed: 0,
+            bytes: 0,
+            seconds: 0.0,
+            bytes_per_second: 0.0,
+        };
+        for (target, result) in results {
+            match result {
+                Ok(Outcome::Downloaded(size)) => {
+                    summary.downloaded += 1;
+                    summary.bytes += size;
+                }
+                Ok(Outcome::Skipped) => summary.skipped += 1,
+                Err(err) => {
+                    error!("{}: {err}", target.path.display());
+                    summary.failed += 1;
+                }
+            }
+        }
+        summary.seconds = start.elapsed().as_secs_f64();
+        if summary.seconds > 0.0 {
+            #[allow(clippy::cast_precision_loss)]
+            let bytes = summary.bytes as f64;
+            summary.bytes_per_second = bytes / summary.seconds;
+        }
+        output(&summary)?;
+
+        if summary.failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to download {} blobs", summary.failed)
+            }));
+        }
+        Ok(())
+    }
+
+    /// Download a single blob, unless the local file already matches it
+    async fn download(
+        &self,
+        container_client: &ContainerClient,
+        target: &Target,
+    ) -> Result<Outcome> {
+        if self.skip_existing && matches_local(target).await? {
+            debug!("skipping {}", target.path.display());
+            return Ok(Outcome::Skipped);
+        }
+
+        if let Some(parent) = target.path.parent() {
+            create_dir_all(parent).await?;
+        }
+        let handle = File::create(&target.path).await?;
+        let conditions = GetConditions {
+            blob_versioning: target.blob_versioning.clone(),
+            ..GetConditions::default()
+        };
+        self.options
+            .download(
+                &container_client.blob_client(&target.name),
+                conditions,
+                Box::pin(handle),
+            )
+            .await?;
+
+        set_modified(&target.path, target.last_modified)?;
+        Ok(Outcome::Downloaded(target.size))
+    }
+
+    /// List the blobs to download, along with the local path of each, and
+    /// count the blobs skipped as their names cannot be used as local paths
+    async fn targets(&self, container_client: &ContainerClient) -> Result<(Vec<Target>, usize)> {
+        let mut builder = container_client
+            .list_blobs()
+            .include_snapshots(self.include_snapshots)
+            .include_versions(self.include_versions);
+        if let Some(prefix) = list_prefix(self.prefix.as_deref(), self.glob.as_ref()) {
+            builder = builder.prefix(prefix);
+        }
+
+        let mut targets = vec![];
+        let mut skipped = 0;
+        let mut paths = HashSet::new();
+        let mut stream = builder.into_stream();
+        while let Some(response) = stream.next().await {
+            for blob in response?.blobs.blobs() {
+                if !matches(self.prefix.as_deref(), self.glob.as_ref(), &blob.name) {
+                    continue;
+                }
+                // Zero length blobs ending in `/` mark directories
+                if blob.name.ends_with('/') {
+                    continue;
+                }
+                let Some(target) = self.target(blob)? else {
+                    warn!(
+                        "skipping {}: name cannot be used as a local path",
+                        blob.name
+                    );
+                    skipped += 1;
+                    continue;
+                };
+                if !paths.insert(target.path.clone()) {
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!(
+                            "multiple blobs would be downloaded to {}",
+                            target.path.display()
+                        )
+                    }));
+                }
+                targets.push(target);
+            }
+        }
+        Ok((targets, skipped))
+    }
+
+    /// The blob, snapshot or version to download, or `None` if its name
+    /// cannot be used as a local path
+    fn target(&self, blob: &Blob) -> Result<Option<Target>> {
+        let (blob_versioning, suffix) = if let Some(snapshot) = &blob.snapshot {
+            let serde_json::Value::String(id) = serde_json::to_value(snapshot)? else {
+                return Err(Error::message(
+                    ErrorKind::DataConversion,
+                    "invalid snapshot",
+                ));
+            };
+            let suffix = format!(".snapshot-{}", id.replace(':', "-"));
+            (Some(BlobVersioning::Snapshot(snapshot.clone())), suffix)
+        } else if let (Some(false), Some(version_id)) = (blob.is_current_version, &blob.version_id)
+        {
+            let suffix = format!(".version-{}", version_id.replace(':', "-"));
+            let version_id = VersionId::new(version_id.clone());
+            (Some(BlobVersioning::VersionId(version_id)), suffix)
+        } else {
+            (None, String::new())
+        };
+
+        let Some(path) = self.local_path(&blob.name, &suffix) else {
+            return Ok(None);
+        };
+        Ok(Some(Target {
+            name: blob.name.clone(),
+            blob_versioning,
+            path,
+            size: blob.properties.content_length,
+            last_modified: blob.properties.last_modified,
+            content_md5: blob
+                .properties
+                .content_md5
+                .as_ref()
+                .map(|md5| *md5.as_slice()),
+        }))
+    }
+
+    /// The local path for a blob, which must stay within the local directory
+    fn local_path(&self, name: &str, suffix: &str) -> Option<PathBuf> {
+        let base = self
+            .prefix
+            .as_deref()
+            .and_then(|prefix| prefix.rfind('/').map(|index| index + 1))
+            .unwrap_or_default();
+        let relative = name.get(base..).unwrap_or(name);
+
+        let relative = if self.flatten {
+            relative.rsplit('/').next().unwrap_or(relative)
+        } else {
+            relative
+        };
+        let mut path = local_path(&self.local_dir, relative)?;
+        if !suffix.is_empty() {
+            let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+            file_name.push(suffix);
+            path.set_file_name(file_name);
+        }
+        Some(path)
+    }
+}
+
+/// Check whether the local file has the size and `Content-MD5` of the blob
+async fn matches_local(target: &Target) -> Result<bool> {
+    let metadata = match fs::metadata(&target.path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    if !metadata.is_file() || metadata.len() != target.size {
+        return Ok(false);
+    }
+    let Some(content_md5) = target.content_md5 else {
+        warn!("{} has no Content-MD5, comparing size only", target.name);
+        return Ok(true);
+    };
+    Ok(file_md5(&target.path).await? == content_md5)
+}
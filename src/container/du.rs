@@ -0,0 +1,236 @@
+use crate::utils::{format_bytes, output, serialized_name};
+use azure_core::error::Result;
+use azure_storage_blobs::{blob::Blob, prelude::ContainerClient};
+use clap::ValueEnum;
+use futures::StreamExt;
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Write};
+
+/// Property used to split the usage of each directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// Access tier
+    Tier,
+    /// Content type
+    ContentType,
+    /// File extension of the blob name
+    Extension,
+}
+
+/// Format of the summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DuFormat {
+    /// The total and the usage of each directory
+    Json,
+    /// A table with a row per directory, followed by the total
+    Table,
+}
+
+/// Summarize the number and size of blobs in each virtual directory
+///
+/// Directories are the segments of blob names separated by `/`.
+#[derive(clap::Parser)]
+pub struct Du {
+    /// only include blobs with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// number of directory levels below the prefix to report
+    #[clap(long, default_value_t = 1)]
+    depth: usize,
+    /// split the usage of each directory by a blob property
+    #[clap(long, value_enum)]
+    by: Option<GroupBy>,
+    /// include snapshots
+    #[clap(long)]
+    include_snapshots: bool,
+    /// include previous versions
+    #[clap(long)]
+    include_versions: bool,
+    /// format of the summary
+    #[clap(long, value_enum, default_value_t = DuFormat::Json)]
+    format: DuFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct Usage {
+    blobs: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    directory: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    total: Usage,
+    directories: Vec<Row>,
+}
+
+impl Du {
+    pub async fn execute(self, container_client: &ContainerClient) -> Result<()> {
+        let mut builder = container_client
+            .list_blobs()
+            .include_snapshots(self.include_snapshots)
+            .include_versions(self.include_versions);
+        if let Some(prefix) = &self.prefix {
+            builder = builder.prefix(prefix.clone());
+        }
+
+        let mut total = Usage::default();
+        let mut usage: HashMap<(String, Option<String>), Usage> = HashMap::new();
+        let mut stream = builder.into_stream();
+        while let Some(response) = stream.next().await {
+            for blob in response?.blobs.blobs() {
+                let size = blob.properties.content_length;
+                total.blobs += 1;
+                total.bytes += size;
+                let entry = usage
+                    .entry((self.directory(&blob.name), self.group(blob)))
+                    .or_default();
+                entry.blobs += 1;
+                entry.bytes += size;
+            }
+        }
+
+        let mut directories = usage
+            .into_iter()
+            .map(|((directory, group), usage)| Row {
+                directory,
+                group,
+                usage,
+            })
+            .collect::<Vec<_>>();
+        directories.sort_by(|a, b| {
+            b.usage
+                .bytes
+                .cmp(&a.usage.bytes)
+                .then_with(|| a.directory.cmp(&b.directory))
+                .then_with(|| a.group.cmp(&b.group))
+        });
+        let report = Report { total, directories };
+
+        match self.format {
+            DuFormat::Json => output(&report),
+            DuFormat::Table => {
+                print!("{}", self.table(&report));
+                Ok(())
+            }
+        }
+    }
+
+    /// The directory a blob is counted in, limited to `depth` levels below
+    /// the prefix
+    fn directory(&self, name: &str) -> String {
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        let rest = name.strip_prefix(prefix).unwrap_or(name);
+        let mut directory = prefix.to_string();
+        // The last segment is the blob's own name
+        let segments = rest.split('/').collect::<Vec<_>>();
+        for segment in segments
+            .iter()
+            .take(segments.len().saturating_sub(1))
+            .take(self.depth)
+        {
+            directory.push_str(segment);
+            directory.push('/');
+        }
+        directory
+    }
+
+    fn group(&self, blob: &Blob) -> Option<String> {
+        let group = match self.by? {
+            GroupBy::Tier => blob
+                .properties
+                .access_tier
+                .as_ref()
+                .map(serialized_name)
+                .unwrap_or_default(),
+            GroupBy::ContentType => blob.properties.content_type.clone(),
+            GroupBy::Extension => {
+                let file_name = blob.name.rsplit('/').next().unwrap_or_default();
+                file_name
+                    .rsplit_once('.')
+                    .filter(|(stem, _)| !stem.is_empty())
+                    .map(|(_, extension)| extension.to_ascii_lowercase())
+                    .unwrap_or_default()
+            }
+        };
+        Some(group)
+    }
+
+    fn table(&self, report: &Report) -> String {
+        let group_header = match self.by {
+            Some(GroupBy::Tier) => Some("TIER"),
+            Some(GroupBy::ContentType) => Some("CONTENT TYPE"),
+            Some(GroupBy::Extension) => Some("EXTENSION"),
+            None => None,
+        };
+        let total = Row {
+            directory: "total".to_string(),
+            group: None,
+            usage: report.total,
+        };
+
+        let rows = report.directories.iter().chain([&total]);
+        let directory_width = rows
+            .clone()
+            .map(|row| display_directory(&row.directory).len())
+            .max()
+            .unwrap_or_default()
+            .max("DIRECTORY".len());
+        let group_width = rows
+            .clone()
+            .filter_map(|row| row.group.as_ref().map(|group| display_group(group).len()))
+            .chain(group_header.map(str::len))
+            .max()
+            .unwrap_or_default();
+
+        let mut table = String::new();
+        let _ = write!(table, "{:<directory_width$}", "DIRECTORY");
+        if let Some(group_header) = group_header {
+            let _ = write!(table, "  {group_header:<group_width$}");
+        }
+        let _ = writeln!(table, "  {:>10}  {:>16}  {:>10}", "BLOBS", "BYTES", "SIZE");
+        for row in rows {
+            let _ = write!(
+                table,
+                "{:<directory_width$}",
+                display_directory(&row.directory)
+            );
+            if group_header.is_some() {
+                let group = row.group.as_deref().map_or("", display_group);
+                let _ = write!(table, "  {group:<group_width$}");
+            }
+            let _ = writeln!(
+                table,
+                "  {:>10}  {:>16}  {:>10}",
+                row.usage.blobs,
+                row.usage.bytes,
+                format_bytes(row.usage.bytes)
+            );
+        }
+        table
+    }
+}
+
+fn display_directory(directory: &str) -> &str {
+    if directory.is_empty() {
+        "/"
+    } else {
+        directory
+    }
+}
+
+fn display_group(group: &str) -> &str {
+    if group.is_empty() {
+        "-"
+    } else {
+        group
+    }
+}
@@ -0,0 +1,439 @@
+use crate::{
+    container::bulk::{list_prefix, matches},
+    paging::{print_marker, Paging},
+    utils::{format_bytes, parse_duration, serialized_name},
+};
+use azure_core::{
+    date::{parse_rfc3339, to_rfc3339},
+    error::{Error, ErrorKind, Result},
+};
+use azure_storage_blobs::{
+    blob::Blob,
+    container::operations::{BlobItem, BlobPrefix, ListBlobsResponse},
+    prelude::ContainerClient,
+};
+use clap::ValueEnum;
+use glob::Pattern;
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    num::NonZeroU32,
+};
+use time::OffsetDateTime;
+
+/// Format of the listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// An array of records, one per blob or virtual directory
+    Json,
+    /// A table with the selected columns
+    Table,
+}
+
+/// A column of the table output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    Name,
+    Size,
+    LastModified,
+    Tier,
+    BlobType,
+    Etag,
+    ContentType,
+    VersionId,
+    Snapshot,
+    Deleted,
+    Tags,
+    Metadata,
+}
+
+/// Property used to sort the listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    Name,
+    Size,
+    LastModified,
+    Tier,
+    BlobType,
+    ContentType,
+}
+
+/// List blobs in a storage container
+///
+/// Each blob is output as a record of its properties. When a delimiter is
+/// set, the virtual directories below the prefix are listed with names ending
+/// in the delimiter.
+#[derive(clap::Parser)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ListBlobs {
+    /// only include blobs with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// group blobs into virtual directories at the specified delimiter
+    #[clap(long)]
+    delimiter: Option<String>,
+    /// max results to return per page
+    #[clap(long, visible_alias = "page-size")]
+    max_results: Option<NonZeroU32>,
+    #[clap(long)]
+    include_snapshots: bool,
+    #[clap(long)]
+    include_metadata: bool,
+    #[clap(long)]
+    include_uncommitted_blobs: bool,
+    #[clap(long)]
+    include_copy: bool,
+    #[clap(long)]
+    include_deleted: bool,
+    #[clap(long)]
+    include_tags: bool,
+    #[clap(long)]
+    include_versions: bool,
+    /// only include blobs with names matching the glob pattern
+    ///
+    /// `*` and `?` do not match `/`, while `**` matches any number of path segments
+    #[clap(long)]
+    glob: Option<Pattern>,
+    /// only include blobs of at least this many bytes
+    #[clap(long)]
+    min_size: Option<u64>,
+    /// only include blobs of at most this many bytes
+    #[clap(long)]
+    max_size: Option<u64>,
+    /// only include blobs last modified after this time
+    ///
+    /// Either a time as described in <https://www.rfc-editor.org/rfc/rfc3339>,
+    /// or a duration before now such as `10d` or `1h30m`
+    #[clap(long)]
+    modified_after: Option<String>,
+    /// only include blobs last modified before this time
+    ///
+    /// Either a time as described in <https://www.rfc-editor.org/rfc/rfc3339>,
+    /// or a duration before now such as `10d` or `1h30m`
+    #[clap(long)]
+    modified_before: Option<String>,
+    /// sort the listing by a blob property rather than by name
+    #[clap(long, value_enum)]
+    sort_by: Option<SortBy>,
+    /// reverse the sort order
+    #[clap(long)]
+    reverse: bool,
+    /// format of the listing
+    #[clap(long, value_enum, default_value_t = ListFormat::Json)]
+    format: ListFormat,
+    /// columns to include in table output
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [Column::Name, Column::Size, Column::LastModified, Column::Tier]
+    )]
+    columns: Vec<Column>,
+    #[clap(flatten)]
+    paging: Paging,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Blob,
+    Prefix,
+}
+
+/// A blob or virtual directory in the listing
+#[derive(Debug, Serialize)]
+struct Entry {
+    name: String,
+    kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    last_modified: Option<OffsetDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blob_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_current_version: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<BTreeMap<String, String>>,
+}
+
+impl Entry {
+    fn from_blob(blob: &Blob) -> Result<Self> {
+        let snapshot = match &blob.snapshot {
+            Some(snapshot) => {
+                let serde_json::Value::String(id) = serde_json::to_value(snapshot)? else {
+                    return Err(Error::message(
+                        ErrorKind::DataConversion,
+                        "invalid snapshot",
+                    ));
+                };
+                Some(id)
+            }
+            None => None,
+        };
+        Ok(Self {
+            name: blob.name.clone(),
+            kind: Kind::Blob,
+            size: Some(blob.properties.content_length),
+            last_modified: Some(blob.properties.last_modified),
+            tier: blob.properties.access_tier.as_ref().map(serialized_name),
+            blob_type: Some(serialized_name(&blob.properties.blob_type)),
+            etag: Some(blob.properties.etag.to_string()),
+            content_type: Some(blob.properties.content_type.clone()),
+            version_id: blob.version_id.clone(),
+            is_current_version: blob.is_current_version,
+            snapshot,
+            deleted: Some(blob.deleted.unwrap_or_default()),
+            tags: blob
+                .tags
+                .clone()
+                .map(|tags| HashMap::from(tags).into_iter().collect()),
+            metadata: blob
+                .metadata
+                .clone()
+                .map(|metadata| metadata.into_iter().collect()),
+        })
+    }
+
+    fn from_prefix(prefix: &BlobPrefix) -> Self {
+        Self {
+            name: prefix.name.clone(),
+            kind: Kind::Prefix,
+            size: None,
+            last_modified: None,
+            tier: None,
+            blob_type: None,
+            etag: None,
+            content_type: None,
+            version_id: None,
+            is_current_version: None,
+            snapshot: None,
+            deleted: None,
+            tags: None,
+            metadata: None,
+        }
+    }
+
+    fn cell(&self, column: Column) -> String {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        let pairs = |values: &Option<BTreeMap<String, String>>| {
+            values
+                .iter()
+                .flatten()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match column {
+            Column::Name => self.name.clone(),
+            Column::Size => self.size.map(format_bytes).unwrap_or_default(),
+            Column::LastModified => self
+                .last_modified
+                .as_ref()
+                .map(to_rfc3339)
+                .unwrap_or_default(),
+            Column::Tier => text(&self.tier),
+            Column::BlobType => text(&self.blob_type),
+            Column::Etag => text(&self.etag),
+            Column::ContentType => text(&self.content_type),
+            Column::VersionId => text(&self.version_id),
+            Column::Snapshot => text(&self.snapshot),
+            Column::Deleted => self
+                .deleted
+                .map(|deleted| deleted.to_string())
+                .unwrap_or_default(),
+            Column::Tags => pairs(&self.tags),
+            Column::Metadata => pairs(&self.metadata),
+        }
+    }
+}
+
+/// Parse a time filter, either as an RFC 3339 time or as a duration before now
+fn parse_cutoff(value: &str) -> Result<OffsetDateTime> {
+    match parse_rfc3339(value) {
+        Ok(time) => Ok(time),
+        Err(_) => Ok(OffsetDateTime::now_utc() - parse_duration(value)?),
+    }
+}
+
+impl ListBlobs {
+    pub async fn execute(self, container_client: &ContainerClient) -> Result<()> {
+        let modified_after = self
+            .modified_after
+            .as_deref()
+            .map(parse_cutoff)
+            .transpose()?;
+        let modified_before = self
+            .modified_before
+            .as_deref()
+            .map(parse_cutoff)
+            .transpose()?;
+
+        let mut builder = container_client
+            .list_blobs()
+            .include_snapshots(self.include_snapshots)
+            .include_metadata(self.include_metadata)
+            .include_uncommitted_blobs(self.include_uncommitted_blobs)
+            .include_copy(self.include_copy)
+            .include_deleted(self.include_deleted)
+            .include_tags(self.include_tags)
+            .include_versions(self.include_versions);
+        if let Some(prefix) = list_prefix(self.prefix.as_deref(), self.glob.as_ref()) {
+            builder = builder.prefix(prefix);
+        }
+        if let Some(delimiter) = &self.delimiter {
+            builder = builder.delimiter(delimiter.clone());
+        }
+        if let Some(max_results) = self.max_results {
+            builder = builder.max_results(max_results);
+        }
+        if let Some(marker) = self.paging.marker() {
+            builder = builder.marker(marker);
+        }
+
+        let entries = |page: ListBlobsResponse| {
+            let mut entries = vec![];
+            for item in &page.blobs.items {
+                if let Some(entry) = self.select(item, modified_after, modified_before)? {
+                    entries.push(entry);
+                }
+            }
+            Ok(entries)
+        };
+
+        // Sorting and the table's column widths need every entry first, so
+        // only unsorted JSON is written as it is listed
+        let sorted = self.sort_by.is_some() || self.reverse;
+        if !sorted && matches!(self.format, ListFormat::Json) {
+            return self
+                .paging
+                .output_stream(builder.into_stream(), entries)
+                .await;
+        }
+
+        let (mut entries, next_marker) =
+            self.paging.collect(builder.into_stream(), entries).await?;
+
+        if let Some(sort_by) = self.sort_by {
+            entries.sort_by(|a, b| compare(a, b, sort_by).then_with(|| a.name.cmp(&b.name)));
+        }
+        if self.reverse {
+            entries.reverse();
+        }
+
+        match self.format {
+            ListFormat::Json => self.paging.output(&entries, next_marker.as_deref()),
+            ListFormat::Table => {
+                print!("{}", self.table(&entries));
+                print_marker(next_marker.as_deref());
+                Ok(())
+            }
+        }
+    }
+
+    /// The entry for a listed blob or virtual directory, if it passes the
+    /// client-side filters
+    fn select(
+        &self,
+        item: &BlobItem,
+        modified_after: Option<OffsetDateTime>,
+        modified_before: Option<OffsetDateTime>,
+    ) -> Result<Option<Entry>> {
+        // Virtual directories have no size or modification time to filter on
+        let filter_properties = self.min_size.is_some()
+            || self.max_size.is_some()
+            || modified_after.is_some()
+            || modified_before.is_some();
+        let entry = match item {
+            BlobItem::Blob(blob) => Entry::from_blob(blob)?,
+            BlobItem::BlobPrefix(_) if filter_properties => return Ok(None),
+            BlobItem::BlobPrefix(prefix) => Entry::from_prefix(prefix),
+        };
+        if !matches(self.prefix.as_deref(), self.glob.as_ref(), &entry.name) {
+            return Ok(None);
+        }
+        let size = entry.size.unwrap_or_default();
+        let last_modified = entry.last_modified.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        if self.min_size.is_some_and(|min_size| size < min_size)
+            || self.max_size.is_some_and(|max_size| size > max_size)
+            || modified_after.is_some_and(|after| last_modified <= after)
+            || modified_before.is_some_and(|before| last_modified >= before)
+        {
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    fn table(&self, entries: &[Entry]) -> String {
+        let header = |column: Column| {
+            column
+                .to_possible_value()
+                .map(|value| value.get_name().to_ascii_uppercase())
+                .unwrap_or_default()
+        };
+        let rows = std::iter::once(self.columns.iter().copied().map(header).collect::<Vec<_>>())
+            .chain(entries.iter().map(|entry| {
+                self.columns
+                    .iter()
+                    .map(|column| entry.cell(*column))
+                    .collect::<Vec<_>>()
+            }))
+            .collect::<Vec<_>>();
+
+        let widths = (0..self.columns.len())
+            .map(|index| {
+                rows.iter()
+                    .filter_map(|row| row.get(index).map(String::len))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+
+        let mut table = String::new();
+        for row in rows {
+            let mut line = String::new();
+            for ((column, cell), width) in self.columns.iter().zip(row).zip(&widths) {
+                if !line.is_empty() {
+                    line.push_str("  ");
+                }
+                if *column == Column::Size {
+                    let _ = write!(line, "{cell:>width$}");
+                } else {
+                    let _ = write!(line, "{cell:<width$}");
+                }
+            }
+            let _ = writeln!(table, "{}", line.trim_end());
+        }
+        table
+    }
+}
+
+fn compare(a: &Entry, b: &Entry, sort_by: SortBy) -> Ordering {
+    match sort_by {
+        SortBy::Name => a.name.cmp(&b.name),
+        SortBy::Size => a.size.cmp(&b.size),
+        SortBy::LastModified => a.last_modified.cmp(&b.last_modified),
+        SortBy::Tier => a.tier.cmp(&b.tier),
+        SortBy::BlobType => a.blob_type.cmp(&b.blob_type),
+        SortBy::ContentType => a.content_type.cmp(&b.content_type),
+    }
+}
@@ -0,0 +1,215 @@
+//! Local directories and the blobs they are transferred to and from
+
+use azure_core::error::Result;
+use azure_storage_blobs::prelude::{BlobContentType, ContainerClient};
+use clap::{Args, ValueEnum};
+use futures::StreamExt;
+use glob::{MatchOptions, Pattern};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+use time::OffsetDateTime;
+use tracing::{debug, warn};
+
+/// How symbolic links in the local directory are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Symlinks {
+    /// Use the target of the link, skipping links that form a loop
+    Follow,
+    /// Ignore symbolic links
+    Skip,
+}
+
+/// Select the files in a local directory
+#[derive(Debug, Args)]
+pub(crate) struct LocalFilter {
+    /// only include files with relative paths matching one of the glob patterns
+    ///
+    /// `*` and `?` do not match `/`, while `**` matches any number of path segments
+    #[clap(long, action = clap::ArgAction::Append)]
+    include: Vec<Pattern>,
+    /// skip files with relative paths matching one of the glob patterns
+    #[clap(long, action = clap::ArgAction::Append)]
+    exclude: Vec<Pattern>,
+    /// how to handle symbolic links
+    #[clap(long, value_enum, default_value_t = Symlinks::Skip)]
+    symlinks: Symlinks,
+    /// include files and directories whose names start with `.`
+    #[clap(long)]
+    include_hidden: bool,
+}
+
+/// A file found in the local directory
+#[derive(Debug)]
+pub(crate) struct LocalFile {
+    pub path: PathBuf,
+    /// Path relative to the directory, using `/` as the separator
+    pub relative: String,
+    pub size: u64,
+    pub modified: OffsetDateTime,
+}
+
+/// Properties of a blob used to compare it with a local file
+#[derive(Debug)]
+pub(crate) struct RemoteBlob {
+    pub size: u64,
+    pub last_modified: OffsetDateTime,
+    pub content_md5: Option<[u8; 16]>,
+    pub etag: String,
+}
+
+impl LocalFilter {
+    /// Find the selected files in the local directory
+    pub(crate) fn walk(&self, local_dir: &Path) -> Result<Vec<LocalFile>> {
+        let mut files = vec![];
+        let mut visited = HashSet::new();
+        visited.insert(fs::canonicalize(local_dir)?);
+        let mut pending = vec![(local_dir.to_path_buf(), String::new())];
+
+        while let Some((dir, relative_dir)) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let file_name = entry.file_name();
+                let Some(file_name) = file_name.to_str() else {
+                    warn!(
+                        "skipping {}: name is not valid UTF-8",
+                        entry.path().display()
+                    );
+                    continue;
+                };
+                if !self.include_hidden && file_name.starts_with('.') {
+                    continue;
+                }
+                let path = entry.path();
+                let relative = format!("{relative_dir}{file_name}");
+
+                let mut metadata = entry.metadata()?;
+                if metadata.is_symlink() {
+                    if self.symlinks == Symlinks::Skip {
+                        debug!("skipping symbolic link {}", path.display());
+                        continue;
+                    }
+                    metadata = match fs::metadata(&path) {
+                        Ok(metadata) => metadata,
+                        Err(err) => {
+                            warn!("skipping {}: {err}", path.display());
+                            continue;
+                        }
+                    };
+                }
+
+                if metadata.is_dir() {
+                    if !visited.insert(fs::canonicalize(&path)?) {
+                        warn!("skipping {}: directory was already visited", path.display());
+                        continue;
+                    }
+                    pending.push((path, format!("{relative}/")));
+                } else if metadata.is_file() && self.selected(&relative) {
+                    files.push(LocalFile {
+                        path,
+                        relative,
+                        size: metadata.len(),
+                        modified: metadata.modified()?.into(),
+                    });
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Check a relative path against the hidden file setting and the include
+    /// and exclude patterns
+    pub(crate) fn selected(&self, relative: &str) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::default()
+        };
+        (self.include_hidden || !relative.split('/').any(|segment| segment.starts_with('.')))
+            && (self.include.is_empty()
+                || self
+                    .include
+                    .iter()
+                    .any(|pattern| pattern.matches_with(relative, options)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches_with(relative, options))
+    }
+}
+
+/// Properties of the blobs under the prefix, by name
+pub(crate) async fn list_remote(
+    container_client: &ContainerClient,
+    prefix: &str,
+) -> Result<HashMap<String, RemoteBlob>> {
+    let mut builder = container_client.list_blobs();
+    if !prefix.is_empty() {
+        builder = builder.prefix(prefix.to_string());
+    }
+    let mut blobs = HashMap::new();
+    let mut stream = builder.into_stream();
+    while let Some(response) = stream.next().await {
+        for blob in response?.blobs.blobs() {
+            blobs.insert(
+                blob.name.clone(),
+                RemoteBlob {
+                    size: blob.properties.content_length,
+                    last_modified: blob.properties.last_modified,
+                    content_md5: blob
+                        .properties
+                        .content_md5
+                        .as_ref()
+                        .map(|md5| *md5.as_slice()),
+                    etag: blob.properties.etag.to_string(),
+                },
+            );
+        }
+    }
+    Ok(blobs)
+}
+
+/// Characters that cannot appear in a file name on this platform, as Windows
+/// treats `\` as a separator and `:` as a drive prefix
+#[cfg(windows)]
+const INVALID_NAME_CHARS: &[char] = &['\\', ':'];
+#[cfg(not(windows))]
+const INVALID_NAME_CHARS: &[char] = &[];
+
+/// The path of a file within the local directory, or `None` for blob names
+/// that cannot be mapped to a path within it
+///
+/// Segments that are empty, `.` or `..`, or that contain characters invalid
+/// on this platform, cannot be mapped.
+pub(crate) fn local_path(local_dir: &Path, relative: &str) -> Option<PathBuf> {
+    let mut path = local_dir.to_path_buf();
+    for segment in relative.split('/') {
+        if matches!(segment, "" | "." | "..") || segment.contains(INVALID_NAME_CHARS) {
+            return None;
+        }
+        path.push(segment);
+    }
+    let below = path.strip_prefix(local_dir).ok()?;
+    below
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then_some(path)
+}
+
+/// Set the modification time of a downloaded file to the blob's last
+/// modified time, so later comparisons see the file as unchanged
+pub(crate) fn set_modified(path: &Path, modified: OffsetDateTime) -> Result<()> {
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(modified.into())?;
+    Ok(())
+}
+
+/// The content type of a file, inferred from its extension
+pub(crate) fn content_type(path: &Path) -> Option<BlobContentType> {
+    mime_guess::from_path(path)
+        .first()
+        .map(|mime| BlobContentType::from(mime.essence_str().to_string()))
+}
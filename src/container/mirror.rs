@@ -0,0 +1,548 @@
+use crate::{
+    rest::RestClient,
+    utils::{output, parse_duration, to_metadata},
+};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    request_options::IfSourceMatchCondition,
+    Url,
+};
+use azure_storage::{
+    shared_access_signature::{service_sas::BlobSasPermissions, SasToken},
+    CloudLocation, StorageCredentialsInner,
+};
+use azure_storage_blobs::{
+    blob::{Blob, BlobType, CopyStatus},
+    prelude::{AccessTier, BlobClient, ClientBuilder, ContainerClient, Tags},
+};
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Duration};
+use time::OffsetDateTime;
+use tokio::time::sleep;
+use tracing::{debug, error, warn};
+
+/// Longest wait between checks on the status of a pending copy
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Copy the blobs in the container to another container, which may be in
+/// another account, using server-side copies
+///
+/// Blobs are compared by size, `Content-MD5` and last modified time, and
+/// metadata, tags and access tier are carried over. Each run writes a report
+/// to the report directory.
+#[derive(clap::Parser)]
+pub struct Mirror {
+    /// URL of the destination container
+    ///
+    /// Include a SAS token in the URL unless authenticating with Azure AD,
+    /// in which case the same identity is used for the destination.
+    #[clap(long)]
+    to: Url,
+    /// only include blobs with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// delete blobs at the destination that do not exist in the source
+    #[clap(long)]
+    delete: bool,
+    /// Number of blobs to copy at a time
+    #[clap(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    /// How long the destination may read the source blobs
+    ///
+    /// Duration as parsed by <https://docs.rs/duration-string/latest/duration_string/>
+    #[clap(long, default_value = "1d")]
+    source_sas_duration: String,
+    /// Directory where the report of each run is written
+    #[clap(long, default_value = ".")]
+    report_dir: PathBuf,
+    /// Only output the planned changes
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Action {
+    /// Copy the blob, along with its metadata, tags and access tier
+    Copy,
+    /// Update the metadata, tags and access tier of a blob whose content is
+    /// unchanged
+    Update,
+    /// Delete a blob that does not exist in the source
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Reason {
+    Missing,
+    Size,
+    Checksum,
+    Newer,
+    Metadata,
+    Tags,
+    Tier,
+    Extra,
+}
+
+#[derive(Debug, Serialize)]
+struct Step {
+    action: Action,
+    reason: Reason,
+    name: String,
+    #[serde(skip)]
+    source: Option<Blob>,
+}
+
+#[derive(Debug, Serialize)]
+struct Plan {
+    steps: Vec<Step>,
+    unchanged: usize,
+    /// Archived blobs, which cannot be copied until they are rehydrated
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    name: String,
+    action: Action,
+    reason: Reason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    #[serde(with = "time::serde::rfc3339")]
+    started: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    finished: OffsetDateTime,
+    source: String,
+    destination: String,
+    copied: usize,
+    updated: usize,
+    deleted: usize,
+    unchanged: usize,
+    skipped: Vec<String>,
+    failed: usize,
+    bytes: u64,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    copied: usize,
+    updated: usize,
+    deleted: usize,
+    unchanged: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    report: PathBuf,
+}
+
+impl Mirror {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let started = OffsetDateTime::now_utc();
+        let destination = destination_client(&self.to, rest_client).await?;
+        let source_blobs = list(container_client, self.prefix.as_deref()).await?;
+        let destination_blobs = list(&destination, self.prefix.as_deref()).await?;
+        let plan = self.plan(source_blobs, &destination_blobs);
+
+        if self.dry_run {
+            return output(&plan);
+        }
+
+        let expiry = started + parse_duration(&self.source_sas_duration)?;
+        let source_query = source_query(container_client, rest_client, expiry).await?;
+
+        let results = stream::iter(plan.steps)
+            .map(|step| {
+                let destination = &destination;
+                let source_query = &source_query;
+                async move {
+                    let result = apply(container_client, destination, source_query, &step).await;
+                    (step, result)
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut destination_url = self.to.clone();
+        destination_url.set_query(None);
+        let mut report = Report {
+            started,
+            finished: started,
+            source: container_client.url()?.to_string(),
+            destination: destination_url.to_string(),
+            copied: 0,
+            updated: 0,
+            deleted: 0,
+            unchanged: plan.unchanged,
+            skipped: plan.skipped,
+            failed: 0,
+            bytes: 0,
+            entries: vec![],
+        };
+        for (step, result) in results {
+            let error = match result {
+                Ok(()) => {
+                    match step.action {
+                        Action::Copy => {
+                            report.copied += 1;
+                            report.bytes += step
+                                .source
+                                .as_ref()
+                                .map_or(0, |blob| blob.properties.content_length);
+                        }
+                        Action::Update => report.updated += 1,
+                        Action::Delete => report.deleted += 1,
+                    }
+                    None
+                }
+                Err(err) => {
+                    error!("{}: {err}", step.name);
+                    report.failed += 1;
+                    Some(err.to_string())
+                }
+            };
+            report.entries.push(Entry {
+                name: step.name,
+                action: step.action,
+                reason: step.reason,
+                error,
+            });
+        }
+        report.entries.sort_by(|a, b| a.name.cmp(&b.name));
+        report.finished = OffsetDateTime::now_utc();
+
+        let report_path = self.report_dir.join(format!(
+            "mirror-{}-{:04}{:02}{:02}T{:02}{:02}{:02}Z.json",
+            container_client.container_name(),
+            started.year(),
+            u8::from(started.month()),
+            started.day(),
+            started.hour(),
+            started.minute(),
+            started.second()
+        ));
+        fs::create_dir_all(&self.report_dir)?;
+        fs::write(&report_path, serde_json::to_vec_pretty(&report)?)?;
+
+        output(&Summary {
+            copied: report.copied,
+            updated: report.updated,
+            deleted: report.deleted,
+            unchanged: report.unchanged,
+            skipped: report.skipped.len(),
+            failed: report.failed,
+            bytes: report.bytes,
+            report: report_path,
+        })?;
+
+        if report.failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to mirror {} blobs", report.failed)
+            }));
+        }
+        Ok(())
+    }
+
+    fn plan(
+        &self,
+        source_blobs: BTreeMap<String, Blob>,
+        destination: &BTreeMap<String, Blob>,
+    ) -> Plan {
+        let mut plan = Plan {
+            steps: vec![],
+            unchanged: 0,
+            skipped: vec![],
+        };
+        if self.delete {
+            for name in destination
+                .keys()
+                .filter(|name| !source_blobs.contains_key(*name))
+            {
+                plan.steps.push(Step {
+                    action: Action::Delete,
+                    reason: Reason::Extra,
+                    name: name.clone(),
+                    source: None,
+                });
+            }
+        }
+        for (name, blob) in source_blobs {
+            let Some((action, reason)) = compare(&blob, destination.get(&name)) else {
+                plan.unchanged += 1;
+                continue;
+            };
+            if action == Action::Copy && blob.properties.access_tier == Some(AccessTier::Archive) {
+                warn!("skipping {name}: archived blobs must be rehydrated before copying");
+                plan.skipped.push(name);
+                continue;
+            }
+            plan.steps.push(Step {
+                action,
+                reason,
+                name,
+                source: Some(blob),
+            });
+        }
+        plan
+    }
+}
+
+/// Compare a source blob with the destination, returning what needs to be
+/// done, if anything
+fn compare(source: &Blob, destination: Option<&Blob>) -> Option<(Action, Reason)> {
+    let Some(destination) = destination else {
+        return Some((Action::Copy, Reason::Missing));
+    };
+    let (source_properties, destination_properties) = (&source.properties, &destination.properties);
+    if source_properties.content_length != destination_properties.content_length {
+        return Some((Action::Copy, Reason::Size));
+    }
+    match (
+        &source_properties.content_md5,
+        &destination_properties.content_md5,
+    ) {
+        (Some(source_md5), Some(destination_md5)) => {
+            if source_md5.as_slice() != destination_md5.as_slice() {
+                return Some((Action::Copy, Reason::Checksum));
+            }
+        }
+        _ => {
+            // Copies complete after the source was last modified, so a
+            // source modified later has changed since it was copied
+            if source_properties.last_modified > destination_properties.last_modified {
+                return Some((Action::Copy, Reason::Newer));
+            }
+        }
+    }
+    if metadata(source) != metadata(destination) {
+        return Some((Action::Update, Reason::Metadata));
+    }
+    if tags(source) != tags(destination) {
+        return Some((Action::Update, Reason::Tags));
+    }
+    if tier(source).is_some_and(|tier| Some(tier) != destination_properties.access_tier) {
+        return Some((Action::Update, Reason::Tier));
+    }
+    None
+}
+
+fn metadata(blob: &Blob) -> BTreeMap<String, String> {
+    blob.metadata
+        .as_ref()
+        .map(|metadata| {
+            metadata
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn tags(blob: &Blob) -> BTreeMap<String, String> {
+    blob.tags
+        .as_ref()
+        .map(|tags| {
+            tags.tag_set
+                .tags
+                .iter()
+                .map(|tag| (tag.key.clone(), tag.value.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The access tier to carry over, which only applies to block blobs
+fn tier(blob: &Blob) -> Option<AccessTier> {
+    if blob.properties.blob_type != BlobType::BlockBlob {
+        return None;
+    }
+    blob.properties.access_tier
+}
+
+async fn apply(
+    source: &ContainerClient,
+    destination: &ContainerClient,
+    source_query: &[(String, String)],
+    step: &Step,
+) -> Result<()> {
+    let blob_client = destination.blob_client(&step.name);
+    let blob = match (step.action, &step.source) {
+        (Action::Delete, _) => {
+            let response = blob_client.delete().await?;
+            debug!("{response:#?}");
+            return Ok(());
+        }
+        (_, Some(blob)) => blob,
+        (_, None) => {
+            return Err(Error::message(ErrorKind::Other, "missing source blob"));
+        }
+    };
+
+    if step.action == Action::Copy {
+        let mut source_url = source.blob_client(&step.name).url()?;
+        source_url.query_pairs_mut().extend_pairs(source_query);
+
+        // The copy fails if the source changed since it was listed
+        let mut builder =
+            blob_client
+                .copy(source_url)
+                .if_source_match(IfSourceMatchCondition::Match(
+                    blob.properties.etag.to_string(),
+                ));
+        if let Some(tier) = tier(blob) {
+            builder = builder.access_tier(tier);
+        }
+        let response = builder.await?;
+        debug!("{response:#?}");
+        wait_for_copy(&blob_client, response.copy_status).await?;
+    } else {
+        // Copies carry the metadata over, so it is only set on updates
+        let response = blob_client
+            .set_metadata()
+            .metadata(to_metadata(metadata(blob).into_iter().collect()))
+            .await?;
+        debug!("{response:#?}");
+        if let Some(tier) = tier(blob) {
+            let response = blob_client.set_blob_tier(tier).await?;
+            debug!("{response:#?}");
+        }
+    }
+
+    // Copies do not carry tags, and replace any tags of the destination
+    let tags = blob.tags.clone().unwrap_or_else(Tags::new);
+    let response = blob_client.set_tags(tags).await?;
+    debug!("{response:#?}");
+    Ok(())
+}
+
+/// Wait for a copy between accounts, which the service may complete
+/// asynchronously
+async fn wait_for_copy(blob_client: &BlobClient, mut status: CopyStatus) -> Result<()> {
+    let mut interval = Duration::from_secs(1);
+    let mut description = None;
+    while status == CopyStatus::Pending {
+        sleep(interval).await;
+        interval = (interval * 2).min(MAX_POLL_INTERVAL);
+        let properties = blob_client.get_properties().await?.blob.properties;
+        status = properties.copy_status.unwrap_or(CopyStatus::Success);
+        description = properties.copy_status_description;
+    }
+    if status != CopyStatus::Success {
+        return Err(Error::with_message(ErrorKind::Other, || {
+            format!("copy {status:?}: {}", description.unwrap_or_default())
+        }));
+    }
+    Ok(())
+}
+
+/// List the blobs in the container, along with their metadata and tags
+async fn list(
+    container_client: &ContainerClient,
+    prefix: Option<&str>,
+) -> Result<BTreeMap<String, Blob>> {
+    let mut builder = container_client
+        .list_blobs()
+        .include_metadata(true)
+        .include_tags(true);
+    if let Some(prefix) = prefix {
+        builder = builder.prefix(prefix.to_string());
+    }
+    let mut blobs = BTreeMap::new();
+    let mut stream = builder.into_stream();
+    while let Some(response) = stream.next().await {
+        for blob in response?.blobs.blobs() {
+            blobs.insert(blob.name.clone(), blob.clone());
+        }
+    }
+    Ok(blobs)
+}
+
+/// Client for the destination container, which uses the SAS token in the URL,
+/// or otherwise the Azure AD identity used for the source
+async fn destination_client(url: &Url, rest_client: &RestClient) -> Result<ContainerClient> {
+    if url.query().is_some() {
+        return ContainerClient::from_sas_url(url);
+    }
+
+    let credentials = rest_client.credentials().clone();
+    if !matches!(
+        &*credentials.0.read().await,
+        StorageCredentialsInner::TokenCredential(_)
+    ) {
+        return Err(Error::message(
+            ErrorKind::Credential,
+            "the destination URL must include a SAS token unless authenticating with Azure AD",
+        ));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::message(ErrorKind::DataConversion, "destination URL has no host"))?;
+    let account = host.split('.').next().unwrap_or(host).to_string();
+    let container = url
+        .path_segments()
+        .and_then(|mut segments| segments.next())
+        .filter(|container| !container.is_empty())
+        .ok_or_else(|| {
+            Error::message(
+                ErrorKind::DataConversion,
+                "destination URL must include the container name",
+            )
+        })?;
+    let uri = format!("{}://{host}", url.scheme());
+    Ok(
+        ClientBuilder::with_location(CloudLocation::Custom { account, uri }, credentials)
+            .container_client(container),
+    )
+}
+
+/// Query parameters that allow the destination to read the source blobs
+async fn source_query(
+    container_client: &ContainerClient,
+    rest_client: &RestClient,
+    expiry: OffsetDateTime,
+) -> Result<Vec<(String, String)>> {
+    let (shared_key, sas_token) = match &*rest_client.credentials().0.read().await {
+        StorageCredentialsInner::Key(..) => (true, None),
+        StorageCredentialsInner::SASToken(query) => (false, Some(query.clone())),
+        _ => (false, None),
+    };
+    if let Some(sas_token) = sas_token {
+        return Ok(sas_token);
+    }
+
+    let permissions = BlobSasPermissions {
+        read: true,
+        ..BlobSasPermissions::default()
+    };
+    let token = if shared_key {
+        container_client
+            .shared_access_signature(permissions, expiry)
+            .await?
+            .token()?
+    } else {
+        let user_deligation_key = container_client
+            .service_client()
+            .get_user_deligation_key(OffsetDateTime::now_utc(), expiry)
+            .await?
+            .user_deligation_key;
+        container_client
+            .user_delegation_shared_access_signature(permissions, &user_deligation_key)
+            .await?
+            .token()?
+    };
+    Ok(url::form_urlencoded::parse(token.as_bytes())
+        .into_owned()
+        .collect())
+}
@@ -0,0 +1,570 @@
+mod batch;
+mod bulk;
+mod delete_blobs;
+mod download;
+mod du;
+mod list;
+mod local;
+mod mirror;
+mod properties;
+mod sync;
+mod tags;
+mod tree;
+mod upload;
+
+use crate::{
+    acl::{get_acl, set_acl, unchanged_since, Acl, PolicyChange, StoredAccessPolicy},
+    blob::{blob_commands, BlobSubCommands},
+    container::{
+        batch::{BatchDelete, BatchSetTier},
+        delete_blobs::DeleteBlobs,
+        download::Download,
+        du::Du,
+        list::ListBlobs,
+        mirror::Mirror,
+        properties::{get_container_properties, RestoreContainer, SetContainerMetadata},
+        sync::SyncDirectory,
+        tags::{AddTags, RemoveTags, SetBlobsMetadata, SetTags},
+        tree::Tree,
+        upload::Upload,
+    },
+    find_blobs_by_tags::{output_blobs_by_tags, TagFilter},
+    lease_exec::{LeaseExec, LeaseTarget},
+    rest::RestClient,
+    sas::SasPermissions,
+    utils::{output, parse_duration, parse_key_val, parse_time, to_metadata, Protocol, TimeFormat},
+};
+use azure_core::{
+    error::{Error, ErrorKind},
+    headers::{Headers, BLOB_PUBLIC_ACCESS},
+    prelude::LeaseDuration,
+    request_options::{IfModifiedSinceCondition, LeaseId},
+    Context, CustomHeaders, Url,
+};
+use azure_storage::clients::ServiceType;
+use azure_storage::shared_access_signature::{service_sas::BlobSasPermissions, SasProtocol};
+use azure_storage_blobs::prelude::{ContainerClient, PublicAccess};
+use clap::Subcommand;
+use serde::Serialize;
+use std::num::NonZeroU32;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Permissions that can be granted by a container's stored access policy
+const CONTAINER_PERMISSIONS: &str = "racwdxyltfmeopi";
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+pub enum ContainerSubCommands {
+    /// Create a storage container
+    Create {
+        /// public access level
+        #[clap(long)]
+        public_access: Option<PublicAccess>,
+
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+        metadata: Option<Vec<(String, String)>>,
+
+        /// default encryption scope for blobs in the container
+        #[clap(long)]
+        default_encryption_scope: Option<String>,
+
+        /// prevent blobs from using an encryption scope other than the container default
+        #[clap(long, requires = "default_encryption_scope")]
+        deny_encryption_scope_override: bool,
+    },
+    /// Get properties for a storage container
+    ///
+    /// The output of this command is serialized as JSON
+    Properties {
+        /// lease id
+        #[clap(long)]
+        lease_id: Option<Uuid>,
+    },
+    /// Get the immutability state of a storage container
+    Immutability {
+        /// lease id
+        #[clap(long)]
+        lease_id: Option<Uuid>,
+    },
+    /// Get the stored access policies and public access level of a storage container
+    GetAcl,
+    /// Change the stored access policies or public access level of a storage container
+    #[command(subcommand_negates_reqs = true)]
+    SetAcl {
+        /// public access level, which is otherwise kept unchanged
+        #[clap(long, required = true)]
+        public_access: Option<PublicAccess>,
+        #[clap(subcommand)]
+        change: Option<PolicyChange>,
+    },
+    /// Delete a storage container
+    Delete {
+        /// lease id
+        #[clap(long)]
+        lease_id: Option<Uuid>,
+    },
+    /// Set the metadata of the container
+    ///
+    /// The metadata replaces all of the container's metadata unless `--merge` is
+    /// specified.  At least one `--metadata` or `--remove` is required, so the
+    /// metadata is never cleared by omitting them.
+    SetMetadata(SetContainerMetadata),
+    /// Restore a soft-deleted container
+    ///
+    /// The deleted versions of a container are listed by
+    /// `account list-containers --include-deleted`.
+    Restore(RestoreContainer),
+    List(ListBlobs),
+    /// Find blobs in the container with tags matching a filter expression
+    ///
+    /// For example: `"project" = 'alpha' AND "priority" >= '2'`
+    FindBlobsByTags {
+        expression: TagFilter,
+        /// max results to return per request
+        #[clap(long)]
+        max_results: Option<NonZeroU32>,
+    },
+    /// Replace the tags on each matching blob
+    SetTags(SetTags),
+    /// Add tags to each matching blob, keeping its other tags
+    AddTags(AddTags),
+    /// Remove tags from each matching blob, keeping its other tags
+    RemoveTags(RemoveTags),
+    /// Replace the metadata on each matching blob
+    SetBlobsMetadata(SetBlobsMetadata),
+    /// Delete the blobs in the container that match the filters
+    ///
+    /// Blobs that have snapshots are skipped unless `--with-snapshots` is specified.
+    DeleteBlobs(DeleteBlobs),
+    /// Delete blobs using the Blob Batch API
+    BatchDelete(BatchDelete),
+    /// Set the access tier of blobs using the Blob Batch API
+    BatchSetTier(BatchSetTier),
+    /// Upload the files in a local directory as block blobs
+    ///
+    /// Blob names are the paths of the files relative to the directory, using
+    /// `/` as the separator, appended to the prefix.
+    Upload(Upload),
+    /// Download the blobs in a container to a local directory
+    ///
+    /// The directory part of the prefix is removed from each blob name, and the
+    /// rest of the name is used as the path of the file within the directory.
+    /// Blobs whose names cannot be used as a path on this platform are skipped.
+    Download(Download),
+    /// Synchronize a local directory with the blobs under a prefix
+    ///
+    /// Files and blobs are compared by size and modification time, or by MD5
+    /// with `--checksum`, and only the ones that differ are transferred.
+    Sync(SyncDirectory),
+    /// Copy the blobs in the container to another container, which may be in
+    /// another account, using server-side copies
+    ///
+    /// Blobs are compared by size, `Content-MD5` and last modified time, and
+    /// metadata, tags and access tier are carried over. Each run writes a
+    /// report to the report directory.
+    Mirror(Mirror),
+    /// Summarize the number and size of blobs in each virtual directory
+    ///
+    /// Directories are the segments of blob names separated by `/`.
+    Du(Du),
+    /// Print the virtual directories and blobs below a prefix as an indented
+    /// tree with sizes, similar to `tree -h`
+    ///
+    /// The size of a directory is the total size of the blobs below it.
+    Tree(Tree),
+    /// Interact with a blob within a storage container
+    Blob {
+        #[clap(subcommand)]
+        subcommand: BlobSubCommands,
+        /// blob name
+        blob_name: String,
+    },
+    /// Generate a SAS URL for a storage container using the User Deligation Key
+    GenerateSas {
+        /// Expiration
+        expiry: String,
+        /// Start time
+        #[clap(long)]
+        start: Option<String>,
+        /// Format used for the start and expiry times
+        #[clap(long, value_enum, default_value_t = TimeFormat::Offset)]
+        time_format: TimeFormat,
+
+        #[clap(long)]
+        ip: Option<String>,
+        #[clap(long)]
+        identifier: Option<String>,
+        #[clap(long)]
+        protocol: Option<Protocol>,
+
+        #[clap(flatten)]
+        sas_permissions: SasPermissions,
+    },
+    /// Acquire a lease on a storage container
+    AcquireLease {
+        /// lease duration in seconds (otherwise uses Infinite)
+        lease_duration: Option<u8>,
+        proposed_lease_id: Option<LeaseId>,
+        lease_id: Option<LeaseId>,
+
+        unmodified_since: Option<String>,
+        modified_since: Option<String>,
+        #[clap(long, default_value = "TimeFormat::Offset")]
+        time_format: TimeFormat,
+    },
+    /// Acquire a lease on a storage container
+    BreakLease {
+        /// Duration as parsed by <https://docs.rs/duration-string/latest/duration_string/>
+        /// Examples include `10d`, `1h`, `1h30m`, and `1h30m10s`
+        lease_break_period: Option<String>,
+        lease_id: Option<LeaseId>,
+        unmodified_since: Option<String>,
+        modified_since: Option<String>,
+        #[clap(long, default_value = "TimeFormat::Offset")]
+        time_format: TimeFormat,
+    },
+    LeaseRelease {
+        lease_id: LeaseId,
+        unmodified_since: Option<String>,
+        modified_since: Option<String>,
+        #[clap(long, default_value = "TimeFormat::Offset")]
+        time_format: TimeFormat,
+    },
+    LeaseRenew {
+        lease_id: LeaseId,
+        unmodified_since: Option<String>,
+        modified_since: Option<String>,
+        #[clap(long, default_value = "TimeFormat::Offset")]
+        time_format: TimeFormat,
+    },
+    LeaseExec(LeaseExec),
+}
+
+/// Container-level immutability, as reported by the container properties
+#[derive(Debug, Serialize)]
+struct ContainerImmutability {
+    has_immutability_policy: bool,
+    has_legal_hold: bool,
+    immutable_storage_with_versioning: bool,
+}
+
+fn container_acl_url(container_client: &ContainerClient) -> azure_core::Result<Url> {
+    let mut url = container_client.url()?;
+    url.query_pairs_mut().append_pair("restype", "container");
+    Ok(url)
+}
+
+/// Get the public access level and stored access policies of the container,
+/// along with the conditions for setting them only while they are unchanged
+///
+/// The SDK's `get_acl` does not request the container's ACL, so this uses the
+/// REST API directly
+async fn get_container_acl(
+    container_client: &ContainerClient,
+    rest_client: &RestClient,
+) -> azure_core::Result<(PublicAccess, Vec<StoredAccessPolicy>, Headers)> {
+    let (headers, policies) = get_acl(
+        rest_client,
+        ServiceType::Blob,
+        container_acl_url(container_client)?,
+    )
+    .await?;
+    let public_access = headers
+        .get_optional_as(&BLOB_PUBLIC_ACCESS)?
+        .unwrap_or(PublicAccess::None);
+    Ok((public_access, policies, unchanged_since(&headers)?))
+}
+
+#[allow(clippy::too_many_lines)]
+pub async fn container_commands(
+    container_client: &ContainerClient,
+    rest_client: &RestClient,
+    subcommand: ContainerSubCommands,
+) -> azure_core::Result<()> {
+    match subcommand {
+        ContainerSubCommands::Create {
+            public_access,
+            metadata,
+            default_encryption_scope,
+            deny_encryption_scope_override,
+        } => {
+            let mut headers = Headers::new();
+            if let Some(default_encryption_scope) = default_encryption_scope {
+                headers.insert("x-ms-default-encryption-scope", default_encryption_scope);
+                headers.insert(
+                    "x-ms-deny-encryption-scope-override",
+                    deny_encryption_scope_override.to_string(),
+                );
+            }
+            let mut context = Context::new();
+            context.insert(CustomHeaders::from(headers));
+
+            let mut builder = container_client.create().context(context);
+            let metadata = metadata.map(to_metadata);
+            args!(builder, metadata, public_access);
+            builder.await?;
+        }
+        ContainerSubCommands::Delete { lease_id } => {
+            let mut builder = container_client.delete();
+            args!(builder, lease_id);
+            builder.await?;
+        }
+        ContainerSubCommands::Properties { lease_id } => {
+            output(&get_container_properties(container_client, rest_client, lease_id).await?)?;
+        }
+        ContainerSubCommands::SetMetadata(set_metadata) => {
+            set_metadata.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::Restore(restore) => {
+            restore.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::Immutability { lease_id } => {
+            let properties =
+                get_container_properties(container_client, rest_client, lease_id).await?;
+            output(&ContainerImmutability {
+                has_immutability_policy: properties.has_immutability_policy,
+                has_legal_hold: properties.has_legal_hold,
+                immutable_storage_with_versioning: properties.immutable_storage_with_versioning,
+            })?;
+        }
+        ContainerSubCommands::GetAcl => {
+            let (public_access, policies, _) =
+                get_container_acl(container_client, rest_client).await?;
+            output(&Acl::new(Some(public_access.to_string()), policies))?;
+        }
+        ContainerSubCommands::SetAcl {
+            public_access,
+            change,
+        } => {
+            // Setting the ACL also sets the public access level, which is
+            // private unless it is specified
+            let (current, mut policies, mut headers) =
+                get_container_acl(container_client, rest_client).await?;
+            if let Some(change) = change {
+                change.apply(&mut policies, CONTAINER_PERMISSIONS)?;
+            }
+            let public_access = public_access.unwrap_or(current);
+            headers.add(public_access);
+            set_acl(
+                rest_client,
+                ServiceType::Blob,
+                container_acl_url(container_client)?,
+                headers,
+                policies.clone(),
+            )
+            .await?;
+            output(&Acl::new(Some(public_access.to_string()), policies))?;
+        }
+        ContainerSubCommands::FindBlobsByTags {
+            expression,
+            max_results,
+        } => {
+            if expression.has_container_condition() {
+                return Err(Error::message(
+                    ErrorKind::Other,
+                    "@container is only supported when searching the account",
+                ));
+            }
+            let mut url = container_client.url()?;
+            url.query_pairs_mut().append_pair("restype", "container");
+            output_blobs_by_tags(rest_client, url, &expression, max_results).await?;
+        }
+        ContainerSubCommands::List(list) => {
+            list.execute(container_client).await?;
+        }
+        ContainerSubCommands::SetTags(set_tags) => {
+            set_tags.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::AddTags(add_tags) => {
+            add_tags.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::RemoveTags(remove_tags) => {
+            remove_tags.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::SetBlobsMetadata(set_metadata) => {
+            set_metadata.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::DeleteBlobs(delete_blobs) => {
+            delete_blobs.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::BatchDelete(batch_delete) => {
+            batch_delete.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::BatchSetTier(batch_set_tier) => {
+            batch_set_tier
+                .execute(container_client, rest_client)
+                .await?;
+        }
+        ContainerSubCommands::Upload(upload) => {
+            upload.execute(container_client).await?;
+        }
+        ContainerSubCommands::Download(download) => {
+            download.execute(container_client).await?;
+        }
+        ContainerSubCommands::Sync(sync) => {
+            sync.execute(container_client).await?;
+        }
+        ContainerSubCommands::Mirror(mirror) => {
+            mirror.execute(container_client, rest_client).await?;
+        }
+        ContainerSubCommands::Du(du) => {
+            du.execute(container_client).await?;
+        }
+        ContainerSubCommands::Tree(tree) => {
+            tree.execute(container_client).await?;
+        }
+        ContainerSubCommands::Blob {
+            subcommand,
+            blob_name,
+        } => {
+            blob_commands(
+                &container_client.blob_client(blob_name),
+                rest_client,
+                subcommand,
+            )
+            .await?;
+        }
+        ContainerSubCommands::GenerateSas {
+            expiry,
+            start,
+            time_format,
+            ip,
+            identifier,
+            protocol,
+            sas_permissions,
+        } => {
+            let expiry = parse_time(&expiry, time_format)?;
+            let start = start
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?
+                .unwrap_or_else(OffsetDateTime::now_utc);
+
+            let permissions = BlobSasPermissions::try_from(sas_permissions)?;
+
+            let user_deligation_key = container_client
+                .service_client()
+                .get_user_deligation_key(start, expiry)
+                .await?
+                .user_deligation_key;
+
+            let mut builder = container_client
+                .user_delegation_shared_access_signature(permissions, &user_deligation_key)
+                .await?;
+            let protocol = protocol.map(|p| match p {
+                Protocol::Https => SasProtocol::Https,
+                Protocol::HttpHttps => SasProtocol::HttpHttps,
+            });
+
+            args!(builder, ip, identifier, protocol);
+
+            let url = container_client.generate_signed_container_url(&builder)?;
+            println!("{url}");
+        }
+        ContainerSubCommands::AcquireLease {
+            lease_duration,
+            lease_id,
+            proposed_lease_id,
+            time_format,
+            unmodified_since,
+            modified_since,
+        } => {
+            let lease_duration =
+                lease_duration.map_or(LeaseDuration::Infinite, LeaseDuration::Seconds);
+            let mut builder = container_client.acquire_lease(lease_duration);
+
+            let modified_since = modified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let unmodified_since = unmodified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let if_modified_since = modified_since
+                .map(IfModifiedSinceCondition::Modified)
+                .or_else(|| unmodified_since.map(IfModifiedSinceCondition::Unmodified));
+
+            args!(builder, lease_id, proposed_lease_id, if_modified_since);
+
+            let result = builder.await?;
+            println!("{result:#?}");
+        }
+        ContainerSubCommands::BreakLease {
+            lease_break_period,
+            lease_id,
+            unmodified_since,
+            modified_since,
+            time_format,
+        } => {
+            let mut builder = container_client.break_lease();
+            let lease_break_period = lease_break_period
+                .as_deref()
+                .map(parse_duration)
+                .transpose()?;
+
+            let modified_since = modified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let unmodified_since = unmodified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let if_modified_since = modified_since
+                .map(IfModifiedSinceCondition::Modified)
+                .or_else(|| unmodified_since.map(IfModifiedSinceCondition::Unmodified));
+
+            args!(builder, lease_id, if_modified_since, lease_break_period);
+
+            let result = builder.await?;
+            println!("{result:#?}");
+        }
+        ContainerSubCommands::LeaseRelease {
+            lease_id,
+            modified_since,
+            unmodified_since,
+            time_format,
+        } => {
+            let mut builder = container_client.container_lease_client(lease_id).release();
+
+            let modified_since = modified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let unmodified_since = unmodified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let if_modified_since = modified_since
+                .map(IfModifiedSinceCondition::Modified)
+                .or_else(|| unmodified_since.map(IfModifiedSinceCondition::Unmodified));
+
+            args!(builder, if_modified_since);
+            let result = builder.await?;
+            println!("{result:#?}");
+        }
+        ContainerSubCommands::LeaseRenew {
+            lease_id,
+            modified_since,
+            unmodified_since,
+            time_format,
+        } => {
+            let mut builder = container_client.container_lease_client(lease_id).renew();
+
+            let modified_since = modified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let unmodified_since = unmodified_since
+                .map(|s| parse_time(&s, time_format))
+                .transpose()?;
+            let if_modified_since = modified_since
+                .map(IfModifiedSinceCondition::Modified)
+                .or_else(|| unmodified_since.map(IfModifiedSinceCondition::Unmodified));
+
+            args!(builder, if_modified_since);
+            let result = builder.await?;
+            println!("{result:#?}");
+        }
+        ContainerSubCommands::LeaseExec(lease_exec) => {
+            lease_exec
+                .execute(LeaseTarget::Container(container_client))
+                .await?;
+        }
+    }
+    Ok(())
+}
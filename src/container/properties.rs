@@ -0,0 +1,209 @@
+use crate::{rest::RestClient, utils::parse_key_val};
+use azure_core::{
+    date,
+    error::Result,
+    headers::{
+        HeaderName, Headers, BLOB_PUBLIC_ACCESS, ETAG, HAS_IMMUTABILITY_POLICY, HAS_LEGAL_HOLD,
+        LAST_MODIFIED, LEASE_DURATION, LEASE_STATE, LEASE_STATUS,
+    },
+    request_options::LeaseId,
+    Method, Url,
+};
+use azure_storage::clients::ServiceType;
+use azure_storage_blobs::prelude::ContainerClient;
+use clap::ArgGroup;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const IMMUTABLE_STORAGE_WITH_VERSIONING: HeaderName =
+    HeaderName::from_static("x-ms-immutable-storage-with-versioning-enabled");
+const DEFAULT_ENCRYPTION_SCOPE: HeaderName =
+    HeaderName::from_static("x-ms-default-encryption-scope");
+const DENY_ENCRYPTION_SCOPE_OVERRIDE: HeaderName =
+    HeaderName::from_static("x-ms-deny-encryption-scope-override");
+const DELETED_CONTAINER_NAME: HeaderName = HeaderName::from_static("x-ms-deleted-container-name");
+const DELETED_CONTAINER_VERSION: HeaderName =
+    HeaderName::from_static("x-ms-deleted-container-version");
+const META_PREFIX: &str = "x-ms-meta-";
+
+/// Properties of a storage container
+///
+/// The SDK's container properties omit the encryption scope and version-level
+/// immutability, so these are read from the REST API directly.
+#[derive(Debug, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct ContainerProperties {
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_modified: Option<OffsetDateTime>,
+    pub etag: Option<String>,
+    pub lease_status: Option<String>,
+    pub lease_state: Option<String>,
+    pub lease_duration: Option<String>,
+    pub public_access: String,
+    pub has_immutability_policy: bool,
+    pub has_legal_hold: bool,
+    pub immutable_storage_with_versioning: bool,
+    pub default_encryption_scope: Option<String>,
+    pub deny_encryption_scope_override: bool,
+    pub metadata: BTreeMap<String, String>,
+}
+
+fn container_url(container_client: &ContainerClient, comp: Option<&str>) -> Result<Url> {
+    let mut url = container_client.url()?;
+    url.query_pairs_mut().append_pair("restype", "container");
+    if let Some(comp) = comp {
+        url.query_pairs_mut().append_pair("comp", comp);
+    }
+    Ok(url)
+}
+
+pub(crate) async fn get_container_properties(
+    container_client: &ContainerClient,
+    rest_client: &RestClient,
+    lease_id: Option<Uuid>,
+) -> Result<ContainerProperties> {
+    let mut headers = Headers::new();
+    headers.add(lease_id.map(LeaseId::from));
+    let response = rest_client
+        .send(
+            ServiceType::Blob,
+            Method::Head,
+            container_url(container_client, None)?,
+            headers,
+            None,
+        )
+        .await?;
+    let headers = response.headers();
+
+    let metadata = headers
+        .iter()
+        .filter_map(|(name, value)| {
+            name.as_str()
+                .strip_prefix(META_PREFIX)
+                .map(|name| (name.to_string(), value.as_str().to_string()))
+        })
+        .collect();
+
+    Ok(ContainerProperties {
+        last_modified: headers
+            .get_optional_str(&LAST_MODIFIED)
+            .map(date::parse_rfc1123)
+            .transpose()?,
+        etag: headers.get_optional_string(&ETAG),
+        lease_status: headers.get_optional_string(&LEASE_STATUS),
+        lease_state: headers.get_optional_string(&LEASE_STATE),
+        lease_duration: headers.get_optional_string(&LEASE_DURATION),
+        // Containers without public access do not return the header
+        public_access: headers
+            .get_optional_string(&BLOB_PUBLIC_ACCESS)
+            .unwrap_or_else(|| "none".to_string()),
+        has_immutability_policy: headers
+            .get_optional_as(&HAS_IMMUTABILITY_POLICY)?
+            .unwrap_or_default(),
+        has_legal_hold: headers
+            .get_optional_as(&HAS_LEGAL_HOLD)?
+            .unwrap_or_default(),
+        immutable_storage_with_versioning: headers
+            .get_optional_as(&IMMUTABLE_STORAGE_WITH_VERSIONING)?
+            .unwrap_or_default(),
+        default_encryption_scope: headers.get_optional_string(&DEFAULT_ENCRYPTION_SCOPE),
+        deny_encryption_scope_override: headers
+            .get_optional_as(&DENY_ENCRYPTION_SCOPE_OVERRIDE)?
+            .unwrap_or_default(),
+        metadata,
+    })
+}
+
+/// Set the metadata of the container
+#[derive(clap::Parser)]
+#[command(group(ArgGroup::new("changes").required(true).multiple(true).args(["metadata", "remove"])))]
+pub struct SetContainerMetadata {
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+    metadata: Vec<(String, String)>,
+    /// keep the container's other metadata, replacing only the specified keys
+    #[clap(long)]
+    merge: bool,
+    /// remove these keys from the container's metadata, keeping the rest
+    #[clap(long, requires = "merge", action = clap::ArgAction::Append)]
+    remove: Vec<String>,
+    /// lease id
+    #[clap(long)]
+    lease_id: Option<Uuid>,
+}
+
+impl SetContainerMetadata {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        // Metadata names are case-insensitive, and are returned in lowercase
+        let mut metadata = if self.merge {
+            get_container_properties(container_client, rest_client, self.lease_id)
+                .await?
+                .metadata
+        } else {
+            BTreeMap::new()
+        };
+        for key in &self.remove {
+            metadata.remove(&key.to_ascii_lowercase());
+        }
+        for (key, value) in self.metadata {
+            metadata.insert(key.to_ascii_lowercase(), value);
+        }
+
+        let mut headers = Headers::new();
+        headers.add(self.lease_id.map(LeaseId::from));
+        for (key, value) in metadata {
+            headers.insert(format!("{META_PREFIX}{key}"), value);
+        }
+        rest_client
+            .send(
+                ServiceType::Blob,
+                Method::Put,
+                container_url(container_client, Some("metadata"))?,
+                headers,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Restore a soft-deleted container
+#[derive(clap::Parser)]
+pub struct RestoreContainer {
+    /// version of the deleted container to restore
+    #[clap(long)]
+    deleted_version: String,
+    /// name of the deleted container, when restoring it under a different name
+    #[clap(long)]
+    deleted_name: Option<String>,
+}
+
+impl RestoreContainer {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let deleted_name = self
+            .deleted_name
+            .unwrap_or_else(|| container_client.container_name().to_string());
+        let mut headers = Headers::new();
+        headers.insert(DELETED_CONTAINER_NAME, deleted_name);
+        headers.insert(DELETED_CONTAINER_VERSION, self.deleted_version);
+        rest_client
+            .send(
+                ServiceType::Blob,
+                Method::Put,
+                container_url(container_client, Some("undelete"))?,
+                headers,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,593 @@
+use crate::{
+    blob::{
+        create_block_blob::{BlobHeaders, BlockBlobOptions},
+        get::GetConditions,
+    },
+    container::local::{
+        content_type, list_remote, local_path, set_modified, LocalFile, LocalFilter, RemoteBlob,
+    },
+    utils::{file_md5, output},
+};
+use azure_core::{
+    base64,
+    error::{Error, ErrorKind, Result},
+};
+use azure_storage_blobs::prelude::{BlobContentMD5, ContainerClient};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use time::OffsetDateTime;
+use tokio::fs::{create_dir_all, remove_file, File};
+use tracing::{debug, error, warn};
+
+/// Name of the state cache kept in the local directory by default
+const STATE_FILE_NAME: &str = ".azs-sync-state.json";
+
+/// Synchronize a local directory with the blobs under a prefix
+///
+/// Files and blobs are compared by size and modification time, or by MD5 with
+/// `--checksum`, and only the ones that differ are transferred.
+#[derive(clap::Parser)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SyncDirectory {
+    /// local directory to synchronize
+    local_dir: PathBuf,
+    /// prefix added to the relative path of each file to form the blob name, such as `dest/`
+    #[clap(long)]
+    prefix: Option<String>,
+    /// update the blobs to match the local directory (the default)
+    #[clap(long, conflicts_with = "to_local")]
+    to_remote: bool,
+    /// update the local directory to match the blobs
+    #[clap(long)]
+    to_local: bool,
+    /// delete blobs or files that do not exist at the source
+    #[clap(long)]
+    delete: bool,
+    /// compare files by MD5 rather than by modification time
+    ///
+    /// Hashes are cached in the state file, and only recomputed for files
+    /// whose size or modification time changed.
+    #[clap(long)]
+    checksum: bool,
+    /// Where hashes of local files are cached
+    ///
+    /// Defaults to `.azs-sync-state.json` in the local directory
+    #[clap(long)]
+    state_file: Option<PathBuf>,
+    /// Number of files to transfer at a time
+    #[clap(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    /// Only output the planned changes
+    ///
+    /// Hashes computed for `--checksum` are still saved to the state file.
+    #[clap(long)]
+    dry_run: bool,
+    #[clap(flatten)]
+    filter: LocalFilter,
+    #[clap(flatten)]
+    options: BlockBlobOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Action {
+    Upload,
+    Download,
+    DeleteRemote,
+    DeleteLocal,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Reason {
+    /// The destination does not exist
+    Missing,
+    /// The sizes differ
+    Size,
+    /// The MD5 hashes differ
+    Checksum,
+    /// The modification times differ, with a file modified after the blob
+    /// when uploading
+    Newer,
+    /// The source does not exist
+    Extra,
+}
+
+/// A single change needed to bring the destination in line with the source
+#[derive(Debug, Serialize)]
+struct Step {
+    action: Action,
+    reason: Reason,
+    name: String,
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip)]
+    relative: String,
+    #[serde(skip)]
+    modified: Option<OffsetDateTime>,
+    #[serde(skip)]
+    content_md5: Option<[u8; 16]>,
+}
+
+#[derive(Debug, Serialize)]
+struct Plan {
+    steps: Vec<Step>,
+    unchanged: usize,
+    /// Blobs whose names cannot be used as local paths
+    skipped: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    uploaded: usize,
+    downloaded: usize,
+    deleted: usize,
+    unchanged: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    seconds: f64,
+    bytes_per_second: f64,
+}
+
+/// Hashes of local files, which are reused while the size and modification
+/// time of the file are unchanged
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    files: BTreeMap<String, CachedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    size: u64,
+    #[serde(with = "time::serde::rfc3339")]
+    modified: OffsetDateTime,
+    /// base64 encoded MD5 of the contents
+    md5: String,
+}
+
+impl State {
+    fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn md5(&self, file: &LocalFile) -> Option<[u8; 16]> {
+        let cached = self.files.get(&file.relative)?;
+        if cached.size != file.size || cached.modified != file.modified {
+            return None;
+        }
+        base64::decode(&cached.md5).ok()?.try_into().ok()
+    }
+
+    fn insert(&mut self, relative: String, size: u64, modified: OffsetDateTime, md5: [u8; 16]) {
+        self.files.insert(
+            relative,
+            CachedFile {
+                size,
+                modified,
+                md5: base64::encode(md5),
+            },
+        );
+    }
+}
+
+impl SyncDirectory {
+    pub async fn execute(self, container_client: &ContainerClient) -> Result<()> {
+        if self.options.client_encrypt() {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "sync compares the contents of files and blobs, which is not supported with client-side encryption",
+            ));
+        }
+        let start = Instant::now();
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        let state_file = self
+            .state_file
+            .clone()
+            .unwrap_or_else(|| self.local_dir.join(STATE_FILE_NAME));
+        let mut state = State::load(&state_file)?;
+
+        let plan = self
+            .plan(container_client, prefix, &mut state, &state_file)
+            .await?;
+
+        if self.dry_run {
+            // The hashes are kept, so a later run does not compute them again
+            state.save(&state_file)?;
+            return output(&plan);
+        }
+
+        let this = &self;
+        let results = stream::iter(plan.steps)
+            .map(|step| async move {
+                let result = this.apply(container_client, &step).await;
+                (step, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut summary = Summary {
+            uploaded: 0,
+            downloaded: 0,
+            deleted: 0,
+            unchanged: plan.unchanged,
+            skipped: plan.skipped,
+            failed: 0,
+            bytes: 0,
+            seconds: 0.0,
+            bytes_per_second: 0.0,
+        };
+        for (step, result) in results {
+            match result {
+                Ok(md5) => {
+                    match step.action {
+                        Action::Upload => summary.uploaded += 1,
+                        Action::Download => summary.downloaded += 1,
+                        Action::DeleteRemote | Action::DeleteLocal => summary.deleted += 1,
+                    }
+                    summary.bytes += step.size.unwrap_or_default();
+                    update_state(&mut state, &step, md5);
+                }
+                Err(err) => {
+                    error!("{}: {err}", step.name);
+                    summary.failed += 1;
+                }
+            }
+        }
+        summary.seconds = start.elapsed().as_secs_f64();
+        if summary.seconds > 0.0 {
+            #[allow(clippy::cast_precision_loss)]
+            let bytes = summary.bytes as f64;
+            summary.bytes_per_second = bytes / summary.seconds;
+        }
+
+        state.save(&state_file)?;
+        output(&summary)?;
+
+        if summary.failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to apply {} changes", summary.failed)
+            }));
+        }
+        Ok(())
+    }
+
+    /// Compare the local directory with the blobs, caching any hashes computed
+    async fn plan(
+        &self,
+        container_client: &ContainerClient,
+        prefix: &str,
+        state: &mut State,
+        state_file: &Path,
+    ) -> Result<Plan> {
+        let local = self
+            .filter
+            .walk(&self.local_dir)?
+            .into_iter()
+            .filter(|file| file.path != state_file)
+            .map(|file| (file.relative.clone(), file))
+            .collect::<HashMap<_, _>>();
+        let remote = list_remote(container_client, prefix)
+            .await?
+            .into_iter()
+            .filter_map(|(name, blob)| {
+                let relative = name.strip_prefix(prefix)?.to_string();
+                self.filter.selected(&relative).then_some((relative, blob))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let hashes = if self.checksum {
+            self.hash(&local, &remote, state).await?
+        } else {
+            HashMap::new()
+        };
+        state
+            .files
+            .retain(|relative, _| local.contains_key(relative));
+        for (relative, md5) in &hashes {
+            if let Some(file) = local.get(relative) {
+                state.insert(relative.clone(), file.size, file.modified, *md5);
+            }
+        }
+
+        if self.to_local {
+            Ok(self.plan_to_local(prefix, &local, &remote, &hashes))
+        } else {
+            Ok(self.plan_to_remote(prefix, &local, &remote, &hashes))
+        }
+    }
+
+    /// Hash the local files that match a blob in size and have a `Content-MD5`
+    /// to compare with, using the cached hash where possible
+    async fn hash(
+        &self,
+        local: &HashMap<String, LocalFile>,
+        remote: &HashMap<String, RemoteBlob>,
+        state: &State,
+    ) -> Result<HashMap<String, [u8; 16]>> {
+        let mut hashes = HashMap::new();
+        let mut pending = vec![];
+        for (relative, file) in local {
+            let Some(blob) = remote.get(relative) else {
+                continue;
+            };
+            if blob.size != file.size || blob.content_md5.is_none() {
+                continue;
+            }
+            match state.md5(file) {
+                Some(md5) => {
+                    hashes.insert(relative.clone(), md5);
+                }
+                None => pending.push(file),
+            }
+        }
+        debug!("hashing {} files", pending.len());
+
+        let computed = stream::iter(pending)
+            .map(|file| async move {
+                let md5 = file_md5(&file.path).await?;
+                Ok::<_, Error>((file.relative.clone(), md5))
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+        for result in computed {
+            let (relative, md5) = result?;
+            hashes.insert(relative, md5);
+        }
+        Ok(hashes)
+    }
+
+    fn plan_to_remote(
+        &self,
+        prefix: &str,
+        local: &HashMap<String, LocalFile>,
+        remote: &HashMap<String, RemoteBlob>,
+        hashes: &HashMap<String, [u8; 16]>,
+    ) -> Plan {
+        let mut steps = vec![];
+        let mut unchanged = 0;
+        for (relative, file) in local {
+            let md5 = hashes.get(relative).copied();
+            let source = Version {
+                size: file.size,
+                modified: file.modified,
+                md5,
+            };
+            let destination = remote.get(relative).map(|blob| Version {
+                size: blob.size,
+                modified: blob.last_modified,
+                md5: blob.content_md5,
+            });
+            let reason = compare(source, destination, true);
+            let Some(reason) = reason else {
+                unchanged += 1;
+                continue;
+            };
+            steps.push(Step {
+                action: Action::Upload,
+                reason,
+                name: format!("{prefix}{relative}"),
+                path: file.path.clone(),
+                size: Some(file.size),
+                relative: relative.clone(),
+                modified: Some(file.modified),
+                content_md5: md5,
+            });
+        }
+        if self.delete {
+            for relative in remote
+                .keys()
+                .filter(|relative| !local.contains_key(*relative))
+            {
+                steps.push(Step {
+                    action: Action::DeleteRemote,
+                    reason: Reason::Extra,
+                    name: format!("{prefix}{relative}"),
+                    path: self.local_dir.join(relative),
+                    size: None,
+                    relative: relative.clone(),
+                    modified: None,
+                    content_md5: None,
+                });
+            }
+        }
+        steps.sort_by(|a, b| a.name.cmp(&b.name));
+        Plan {
+            steps,
+            unchanged,
+            skipped: 0,
+        }
+    }
+
+    fn plan_to_local(
+        &self,
+        prefix: &str,
+        local: &HashMap<String, LocalFile>,
+        remote: &HashMap<String, RemoteBlob>,
+        hashes: &HashMap<String, [u8; 16]>,
+    ) -> Plan {
+        let mut steps = vec![];
+        let mut unchanged = 0;
+        let mut skipped = 0;
+        for (relative, blob) in remote {
+            // Zero length blobs ending in `/` mark directories
+            if relative.is_empty() || relative.ends_with('/') {
+                continue;
+            }
+            let source = Version {
+                size: blob.size,
+                modified: blob.last_modified,
+                md5: blob.content_md5,
+            };
+            let destination = local.get(relative).map(|file| Version {
+                size: file.size,
+                modified: file.modified,
+                md5: hashes.get(relative).copied(),
+            });
+            let reason = compare(source, destination, false);
+            let Some(reason) = reason else {
+                unchanged += 1;
+                continue;
+            };
+            let name = format!("{prefix}{relative}");
+            let Some(path) = local_path(&self.local_dir, relative) else {
+                warn!("skipping {name}: name cannot be used as a local path");
+                skipped += 1;
+                continue;
+            };
+            steps.push(Step {
+                action: Action::Download,
+                reason,
+                name,
+                path,
+                size: Some(blob.size),
+                relative: relative.clone(),
+                modified: Some(blob.last_modified),
+                content_md5: blob.content_md5,
+            });
+        }
+        if self.delete {
+            for (relative, file) in local
+                .iter()
+                .filter(|(relative, _)| !remote.contains_key(*relative))
+            {
+                steps.push(Step {
+                    action: Action::DeleteLocal,
+                    reason: Reason::Extra,
+                    name: format!("{prefix}{relative}"),
+                    path: file.path.clone(),
+                    size: None,
+                    relative: relative.clone(),
+                    modified: None,
+                    content_md5: None,
+                });
+            }
+        }
+        steps.sort_by(|a, b| a.name.cmp(&b.name));
+        Plan {
+            steps,
+            unchanged,
+            skipped,
+        }
+    }
+
+    /// Apply a single step, returning the MD5 of the local file when known
+    async fn apply(
+        &self,
+        container_client: &ContainerClient,
+        step: &Step,
+    ) -> Result<Option<[u8; 16]>> {
+        let blob_client = container_client.blob_client(&step.name);
+        match step.action {
+            Action::Upload => {
+                // Blobs uploaded in blocks only have a `Content-MD5` if one
+                // is provided, which later syncs use to detect changes
+                let md5 = match step.content_md5 {
+                    Some(md5) => md5,
+                    None => file_md5(&step.path).await?,
+                };
+                let headers = BlobHeaders {
+                    content_type: content_type(&step.path),
+                    content_md5: Some(BlobContentMD5::from(md5)),
+                    ..BlobHeaders::default()
+                };
+                self.options
+                    .upload(&blob_client, &step.path, headers)
+                    .await?;
+                Ok(Some(md5))
+            }
+            Action::Download => {
+                if let Some(parent) = step.path.parent() {
+                    create_dir_all(parent).await?;
+                }
+                let handle = File::create(&step.path).await?;
+                self.options
+                    .download_options()
+                    .download(&blob_client, GetConditions::default(), Box::pin(handle))
+                    .await?;
+                if let Some(modified) = step.modified {
+                    set_modified(&step.path, modified)?;
+                }
+                Ok(step.content_md5)
+            }
+            Action::DeleteRemote => {
+                let response = blob_client.delete().await?;
+                debug!("{response:#?}");
+                Ok(None)
+            }
+            Action::DeleteLocal => {
+                remove_file(&step.path).await?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Size, modification time and MD5 (when known) of a file or blob
+#[derive(Debug, Clone, Copy)]
+struct Version {
+    size: u64,
+    modified: OffsetDateTime,
+    md5: Option<[u8; 16]>,
+}
+
+/// Compare the source with the destination, returning why the destination
+/// needs to be replaced, if it does.
+///
+/// The modification times of blobs are set by the service, so uploads only
+/// replace blobs last modified before the file, while downloads set the
+/// file's modification time to match the blob.
+fn compare(source: Version, destination: Option<Version>, to_remote: bool) -> Option<Reason> {
+    let Some(destination) = destination else {
+        return Some(Reason::Missing);
+    };
+    if source.size != destination.size {
+        return Some(Reason::Size);
+    }
+    if let (Some(source_md5), Some(destination_md5)) = (source.md5, destination.md5) {
+        return (source_md5 != destination_md5).then_some(Reason::Checksum);
+    }
+    let changed = if to_remote {
+        source.modified > destination.modified
+    } else {
+        source.modified != destination.modified
+    };
+    changed.then_some(Reason::Newer)
+}
+
+/// Record the hash of a file that was transferred, or forget a deleted one
+fn update_state(state: &mut State, step: &Step, md5: Option<[u8; 16]>) {
+    match (step.action, md5) {
+        (Action::DeleteLocal, _) => {
+            state.files.remove(&step.relative);
+        }
+        (Action::Upload | Action::Download, Some(md5)) => {
+            let Ok(metadata) = fs::metadata(&step.path) else {
+                return;
+            };
+            let Ok(modified) = metadata.modified() else {
+                return;
+            };
+            state.insert(step.relative.clone(), metadata.len(), modified.into(), md5);
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    blob::client_encryption::METADATA_KEY,
+    container::bulk::{BlobFilter, BulkOptions},
+    rest::RestClient,
+    utils::{http_status, parse_key_val, to_metadata, to_tags},
+};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    request_options::{IfMatchCondition, IfTags},
+    StatusCode,
+};
+use azure_storage_blobs::prelude::{BlobClient, ContainerClient, Tags};
+use std::collections::HashMap;
+use tracing::debug;
+
+/// How many times to retry merging tags or metadata when a blob changes
+/// concurrently
+const MAX_MERGE_ATTEMPTS: usize = 5;
+
+/// Replace the tags on each matching blob
+#[derive(clap::Parser)]
+pub struct SetTags {
+    #[clap(flatten)]
+    filter: BlobFilter,
+    #[clap(long, required = true, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+    tags: Vec<(String, String)>,
+    #[clap(flatten)]
+    options: BulkOptions,
+}
+
+impl SetTags {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let tags = to_tags(self.tags);
+        self.options
+            .for_each_blob(&self.filter, container_client, rest_client, |blob_client| {
+                let tags = tags.clone();
+                async move {
+                    let response = blob_client.set_tags(tags).await?;
+                    debug!("{response:#?}");
+                    Ok(())
+                }
+            })
+            .await
+    }
+}
+
+/// Add tags to each matching blob, keeping its other tags
+#[derive(clap::Parser)]
+pub struct AddTags {
+    #[clap(flatten)]
+    filter: BlobFilter,
+    #[clap(long, required = true, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+    tags: Vec<(String, String)>,
+    #[clap(flatten)]
+    options: BulkOptions,
+}
+
+impl AddTags {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let added = &self.tags;
+        self.options
+            .for_each_blob(&self.filter, container_client, rest_client, |blob_client| {
+                merge_tags(blob_client, move |tags| {
+                    tags.extend(added.iter().cloned());
+                })
+            })
+            .await
+    }
+}
+
+/// Remove tags from each matching blob, keeping its other tags
+#[derive(clap::Parser)]
+pub struct RemoveTags {
+    #[clap(flatten)]
+    filter: BlobFilter,
+    /// tag keys to remove
+    #[clap(long, required = true, action = clap::ArgAction::Append)]
+    keys: Vec<String>,
+    #[clap(flatten)]
+    options: BulkOptions,
+}
+
+impl RemoveTags {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let keys = &self.keys;
+        self.options
+            .for_each_blob(&self.filter, container_client, rest_client, |blob_client| {
+                merge_tags(blob_client, move |tags| {
+                    tags.retain(|key, _| !keys.contains(key));
+                })
+            })
+            .await
+    }
+}
+
+/// Replace the metadata on each matching blob
+///
+/// The `encryptiondata` entry written by client-side encryption is always
+/// kept, as the blob cannot be decrypted without it.
+#[derive(clap::Parser)]
+pub struct SetBlobsMetadata {
+    #[clap(flatten)]
+    filter: BlobFilter,
+    #[clap(long, required = true, value_name = "KEY=VALUE", value_parser = parse_key_val::<String, String>, action = clap::ArgAction::Append)]
+    metadata: Vec<(String, String)>,
+    /// keep the blob's other metadata, replacing only the specified keys
+    #[clap(long)]
+    merge: bool,
+    #[clap(flatten)]
+    options: BulkOptions,
+}
+
+impl SetBlobsMetadata {
+    pub async fn execute(
+        self,
+        container_client: &ContainerClient,
+        rest_client: &RestClient,
+    ) -> Result<()> {
+        let merge = self.merge;
+        let updated = &self.metadata;
+        self.options
+            .for_each_blob(&self.filter, container_client, rest_client, |blob_client| {
+                merge_metadata(blob_client, move |metadata| {
+                    if !merge {
+                        metadata.retain(|key, _| key.eq_ignore_ascii_case(METADATA_KEY));
+                    }
+                    metadata.extend(updated.iter().cloned());
+                })
+            })
+            .await
+    }
+}
+
+/// Update the blob's metadata based on its current metadata.
+///
+/// Setting metadata replaces all of it, so the update is conditioned on the
+/// etag the metadata was read with.  If another writer changes the blob in
+/// between, the merge is retried with the new metadata.
+async fn merge_metadata<F>(blob_client: BlobClient, update: F) -> Result<()>
+where
+    F: Fn(&mut HashMap<String, String>),
+{
+    for _ in 0..MAX_MERGE_ATTEMPTS {
+        let blob = blob_client.get_properties().await?.blob;
+        let current = blob.metadata.unwrap_or_default();
+        let mut metadata = current.clone();
+        update(&mut metadata);
+        if metadata == current {
+            return Ok(());
+        }
+
+        let response = blob_client
+            .set_metadata()
+            .metadata(to_metadata(metadata.into_iter().collect()))
+            .if_match(IfMatchCondition::Match(blob.properties.etag.to_string()))
+            .await;
+        match response {
+            Ok(response) => {
+                debug!("{response:#?}");
+                return Ok(());
+            }
+            Err(err) if http_status(&err) == Some(StatusCode::PreconditionFailed) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(Error::with_message(ErrorKind::Other, || {
+        format!(
+            "metadata changed during each of {MAX_MERGE_ATTEMPTS} attempts to update {}",
+            blob_client.blob_name()
+        )
+    }))
+}
+
+/// Update the blob's tags based on its current tags.
+///
+/// Setting tags replaces all of them, so the update is conditioned on every
+/// tag that was read.  If another writer changes or removes any of them in
+/// between, the merge is retried with the new tags.
+///
+/// Tag conditions cannot test that a tag is absent, and setting tags does not
+/// change the blob's etag, so a tag added by another writer in between is not
+/// detected.  A blob without any tags cannot be matched by a tag condition, so
+/// that update is unconditional.
+async fn merge_tags<F>(blob_client: BlobClient, update: F) -> Result<()>
+where
+    F: Fn(&mut HashMap<String, String>),
+{
+    for _ in 0..MAX_MERGE_ATTEMPTS {
+        let current: HashMap<String, String> = blob_client.get_tags().await?.tags.into();
+        let mut tags = current.clone();
+        update(&mut tags);
+        if tags == current {
+            return Ok(());
+        }
+
+        let mut builder = blob_client.set_tags(Tags::from(tags));
+        if !current.is_empty() {
+            builder = builder.if_tags(IfTags::new(tags_condition(&current)));
+        }
+        match builder.await {
+            Ok(response) => {
+                debug!("{response:#?}");
+                return Ok(());
+            }
+            Err(err) if http_status(&err) == Some(StatusCode::PreconditionFailed) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(Error::with_message(ErrorKind::Other, || {
+        format!(
+            "tags changed during each of {MAX_MERGE_ATTEMPTS} attempts to update {}",
+            blob_client.blob_name()
+        )
+    }))
+}
+
+/// Tag filter expression that only matches while each of the tags is unchanged
+fn tags_condition(tags: &HashMap<String, String>) -> String {
+    tags.iter()
+        .map(|(key, value)| format!("\"{key}\" = '{value}'"))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
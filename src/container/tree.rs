@@ -0,0 +1,167 @@
+use crate::utils::format_bytes;
+use azure_core::error::Result;
+use azure_storage_blobs::{container::operations::BlobItem, prelude::ContainerClient};
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+use std::fmt::Write;
+
+const DELIMITER: &str = "/";
+
+/// Print the virtual directories and blobs below a prefix as an indented tree
+/// with sizes, similar to `tree -h`
+///
+/// The size of a directory is the total size of the blobs below it.
+#[derive(clap::Parser)]
+pub struct Tree {
+    /// only include blobs with the specified prefix
+    #[clap(long)]
+    prefix: Option<String>,
+    /// number of directory levels below the prefix to descend into
+    ///
+    /// Directories at the last level are shown with their total size but
+    /// without their contents.
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    depth: Option<u32>,
+}
+
+/// A blob or virtual directory in the tree
+struct Node {
+    /// Name relative to the parent directory, ending in `/` for directories
+    name: String,
+    size: u64,
+    directory: bool,
+    /// Contents of a directory, or `None` for blobs and for directories
+    /// below the depth limit
+    children: Option<Vec<Node>>,
+}
+
+#[derive(Default)]
+struct Counts {
+    directories: u64,
+    blobs: u64,
+}
+
+impl Tree {
+    pub async fn execute(self, container_client: &ContainerClient) -> Result<()> {
+        let prefix = self.prefix.unwrap_or_default();
+        let children = list_level(container_client, prefix.clone(), self.depth).await?;
+        let size = children.iter().map(|node| node.size).sum();
+
+        let root = if prefix.is_empty() { "." } else { &prefix };
+        let mut tree = format!("[{:>10}]  {root}\n", format_bytes(size));
+        let mut counts = Counts::default();
+        write_nodes(&mut tree, &children, "", &mut counts);
+        let _ = writeln!(
+            tree,
+            "\n{} directories, {} blobs, {}",
+            counts.directories,
+            counts.blobs,
+            format_bytes(size)
+        );
+        print!("{tree}");
+        Ok(())
+    }
+}
+
+/// List the blobs and directories directly below a prefix, descending into
+/// directories until the remaining depth runs out
+fn list_level(
+    container_client: &ContainerClient,
+    prefix: String,
+    depth: Option<u32>,
+) -> BoxFuture<'_, Result<Vec<Node>>> {
+    async move {
+        let mut builder = container_client.list_blobs().delimiter(DELIMITER);
+        if !prefix.is_empty() {
+            builder = builder.prefix(prefix.clone());
+        }
+
+        let mut items = vec![];
+        let mut stream = builder.into_stream();
+        while let Some(response) = stream.next().await {
+            items.extend(response?.blobs.items);
+        }
+
+        let mut nodes = vec![];
+        for item in items {
+            let node = match item {
+                BlobItem::Blob(blob) => Node {
+                    name: relative_name(&blob.name, &prefix),
+                    size: blob.properties.content_length,
+                    directory: false,
+                    children: None,
+                },
+                BlobItem::BlobPrefix(directory) => {
+                    let name = relative_name(&directory.name, &prefix);
+                    match depth {
+                        Some(depth) if depth <= 1 => Node {
+                            name,
+                            size: total_size(container_client, directory.name).await?,
+                            directory: true,
+                            children: None,
+                        },
+                        _ => {
+                            let children = list_level(
+                                container_client,
+                                directory.name,
+                                depth.map(|depth| depth - 1),
+                            )
+                            .await?;
+                            Node {
+                                name,
+                                size: children.iter().map(|node| node.size).sum(),
+                                directory: true,
+                                children: Some(children),
+                            }
+                        }
+                    }
+                }
+            };
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+    .boxed()
+}
+
+/// The total size of all blobs below a prefix
+async fn total_size(container_client: &ContainerClient, prefix: String) -> Result<u64> {
+    let mut size = 0;
+    let mut stream = container_client.list_blobs().prefix(prefix).into_stream();
+    while let Some(response) = stream.next().await {
+        size += response?
+            .blobs
+            .blobs()
+            .map(|blob| blob.properties.content_length)
+            .sum::<u64>();
+    }
+    Ok(size)
+}
+
+fn relative_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix).unwrap_or(name).to_string()
+}
+
+fn write_nodes(tree: &mut String, nodes: &[Node], indent: &str, counts: &mut Counts) {
+    for (index, node) in nodes.iter().enumerate() {
+        let last = index + 1 == nodes.len();
+        let (branch, continuation) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let _ = writeln!(
+            tree,
+            "{indent}{branch}[{:>10}]  {}",
+            format_bytes(node.size),
+            node.name
+        );
+        if node.directory {
+            counts.directories += 1;
+        } else {
+            counts.blobs += 1;
+        }
+        if let Some(children) = &node.children {
+            write_nodes(tree, children, &format!("{indent}{continuation}"), counts);
+        }
+    }
+}
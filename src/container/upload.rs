@@ -0,0 +1,214 @@
+use crate::{
+    blob::create_block_blob::{BlobHeaders, BlockBlobOptions},
+    container::local::{content_type, list_remote, LocalFile, LocalFilter, RemoteBlob},
+    utils::{file_md5, http_status, output},
+};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    request_options::IfMatchCondition,
+    StatusCode,
+};
+use azure_storage_blobs::prelude::{BlobContentMD5, ContainerClient};
+use clap::ValueEnum;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+use tracing::{debug, error};
+
+/// When to replace a blob that already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Overwrite {
+    /// Never replace existing blobs
+    Never,
+    /// Always replace existing blobs
+    Always,
+    /// Replace blobs last modified before the local file
+    IfNewer,
+    /// Replace blobs whose size or `Content-MD5` differ from the local file
+    ///
+    /// Blobs without a `Content-MD5` are replaced if the local file is newer
+    IfDifferent,
+}
+
+/// Upload the files in a local directory as block blobs
+///
+/// Blob names are the paths of the files relative to the directory, using `/`
+/// as the separator, appended to the prefix.
+#[derive(clap::Parser)]
+pub struct Upload {
+    /// local directory to upload
+    local_dir: PathBuf,
+    /// prefix added to the name of each blob, such as `dest/`
+    #[clap(long)]
+    prefix: Option<String>,
+    #[clap(flatten)]
+    filter: LocalFilter,
+    /// when to replace blobs that already exist
+    ///
+    /// Unless always replacing, blobs created or changed after the container
+    /// is listed are skipped rather than overwritten
+    #[clap(long, value_enum, default_value_t = Overwrite::Always)]
+    overwrite: Overwrite,
+    /// Number of files to upload at a time
+    #[clap(long, default_value_t = 8, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    #[clap(flatten)]
+    options: BlockBlobOptions,
+}
+
+#[derive(Debug)]
+enum Outcome {
+    Uploaded(u64),
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    uploaded: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+    seconds: f64,
+    bytes_per_second: f64,
+}
+
+impl Upload {
+    pub async fn execute(self, container_client: &ContainerClient) -> Result<()> {
+        let start = Instant::now();
+        let files = self.filter.walk(&self.local_dir)?;
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        let existing = if self.overwrite == Overwrite::Always {
+            HashMap::new()
+        } else {
+            list_remote(container_client, prefix).await?
+        };
+
+        let this = &self;
+        let existing = &existing;
+        let results = stream::iter(files)
+            .map(|file| {
+                let name = format!("{prefix}{}", file.relative);
+                let remote = existing.get(&name);
+                async move {
+                    let result = this.upload(container_client, &name, &file, remote).await;
+                    (name, result)
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut summary = Summary {
+            uploaded: 0,
+            skipped: 0,
+            failed: 0,
+            bytes: 0,
+            seconds: 0.0,
+            bytes_per_second: 0.0,
+        };
+        for (name, result) in results {
+            match result {
+                Ok(Outcome::Uploaded(size)) => {
+                    summary.uploaded += 1;
+                    summary.bytes += size;
+                }
+                Ok(Outcome::Skipped) => summary.skipped += 1,
+                Err(err) => {
+                    error!("{name}: {err}");
+                    summary.failed += 1;
+                }
+            }
+        }
+        summary.seconds = start.elapsed().as_secs_f64();
+        if summary.seconds > 0.0 {
+            #[allow(clippy::cast_precision_loss)]
+            let bytes = summary.bytes as f64;
+            summary.bytes_per_second = bytes / summary.seconds;
+        }
+        output(&summary)?;
+
+        if summary.failed > 0 {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("failed to upload {} files", summary.failed)
+            }));
+        }
+        Ok(())
+    }
+
+    /// Upload a single file, unless the existing blob should be kept
+    ///
+    /// The upload is conditioned on the blob being unchanged since it was
+    /// listed, so a blob created or replaced since then is skipped rather
+    /// than overwritten.
+    async fn upload(
+        &self,
+        container_client: &ContainerClient,
+        name: &str,
+        file: &LocalFile,
+        remote: Option<&RemoteBlob>,
+    ) -> Result<Outcome> {
+        let mut content_md5 = None;
+        let if_match = match remote {
+            _ if self.overwrite == Overwrite::Always => None,
+            Some(remote) => Some(IfMatchCondition::Match(remote.etag.clone())),
+            None => Some(IfMatchCondition::NotMatch("*".to_string())),
+        };
+        if let Some(remote) = remote {
+            let replace = match self.overwrite {
+                Overwrite::Always => true,
+                Overwrite::Never => false,
+                Overwrite::IfNewer => file.modified > remote.last_modified,
+                Overwrite::IfDifferent => {
+                    if file.size != remote.size {
+                        true
+                    } else if let Some(remote_md5) = remote.content_md5 {
+                        let local_md5 = file_md5(&file.path).await?;
+                        content_md5 = Some(local_md5);
+                        local_md5 != remote_md5
+                    } else {
+                        file.modified > remote.last_modified
+                    }
+                }
+            };
+            if !replace {
+                debug!("skipping {name}");
+                return Ok(Outcome::Skipped);
+            }
+        }
+
+        // Blobs uploaded in blocks only have a `Content-MD5` if one is
+        // provided, which later uploads comparing contents need
+        if self.overwrite == Overwrite::IfDifferent
+            && content_md5.is_none()
+            && !self.options.client_encrypt()
+        {
+            content_md5 = Some(file_md5(&file.path).await?);
+        }
+
+        let headers = BlobHeaders {
+            content_type: content_type(&file.path),
+            content_md5: content_md5.map(BlobContentMD5::from),
+            if_match,
+            ..BlobHeaders::default()
+        };
+        let conditional = headers.if_match.is_some();
+        let result = self
+            .options
+            .upload(&container_client.blob_client(name), &file.path, headers)
+            .await;
+        match result {
+            Ok(()) => Ok(Outcome::Uploaded(file.size)),
+            Err(err)
+                if conditional
+                    && matches!(
+                        http_status(&err),
+                        Some(StatusCode::Conflict | StatusCode::PreconditionFailed)
+                    ) =>
+            {
+                debug!("skipping {name}, which changed since it was listed");
+                Ok(Outcome::Skipped)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
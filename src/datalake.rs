@@ -1,10 +1,15 @@
 use crate::{
-    args,
+    paging::Paging,
+    rest::RestClient,
+    sas::{directory_sas, SasOptions, SasPermissions},
     utils::{parse_key_val, to_properties},
 };
-use azure_storage_datalake::clients::DataLakeClient;
+use azure_storage::shared_access_signature::service_sas::{BlobSasPermissions, SasKey};
+use azure_storage_blobs::prelude::BlobServiceClient;
+use azure_storage_datalake::{clients::DataLakeClient, prelude::PathClient};
 use clap::Subcommand;
 use std::num::NonZeroU32;
+use time::OffsetDateTime;
 
 #[derive(Subcommand)]
 pub enum DatalakeSubCommands {
@@ -40,12 +45,15 @@ pub enum FileSystemSubCommands {
     ListPaths {
         #[clap(long)]
         recursive: Option<bool>,
-        #[clap(long)]
+        /// max results to return per page
+        #[clap(long, visible_alias = "page-size")]
         max_results: Option<NonZeroU32>,
         #[clap(long)]
         upn: Option<bool>,
         #[clap(long)]
         directory: Option<String>,
+        #[clap(flatten)]
+        paging: Paging,
     },
     /// Perform operations on the specified directory
     Directory {
@@ -74,22 +82,44 @@ pub enum DirectorySubCommands {
     ListPaths {
         #[clap(long)]
         recursive: Option<bool>,
-        #[clap(long)]
+        /// max results to return per page
+        #[clap(long, visible_alias = "page-size")]
         max_results: Option<NonZeroU32>,
         #[clap(long)]
         upn: Option<bool>,
         #[clap(long)]
         directory: Option<String>,
+        #[clap(flatten)]
+        paging: Paging,
+    },
+    /// Generate a SAS URL for the directory
+    ///
+    /// The SAS is signed with the account access key when `--access-key` is
+    /// specified, and with a user delegation key otherwise
+    GenerateSas {
+        #[clap(flatten)]
+        options: SasOptions,
+        /// Depth of the directory, which defaults to the number of segments in its path
+        #[clap(long)]
+        depth: Option<usize>,
+        /// Stored access policy on the file system (requires `--access-key`)
+        #[clap(long)]
+        identifier: Option<String>,
+        #[clap(flatten)]
+        sas_permissions: SasPermissions,
     },
 }
 
+#[allow(clippy::too_many_lines)]
 pub async fn datalake_commands(
     service_client: &DataLakeClient,
+    blob_service_client: &BlobServiceClient,
+    rest_client: &RestClient,
     subcommand: DatalakeSubCommands,
 ) -> azure_core::Result<()> {
     match subcommand {
         DatalakeSubCommands::FileSystem { name, subcommand } => {
-            let filesystem = service_client.file_system_client(name);
+            let filesystem = service_client.file_system_client(&name);
             match subcommand {
                 FileSystemSubCommands::Create { properties } => {
                     let properties = properties.map(to_properties);
@@ -108,16 +138,21 @@ pub async fn datalake_commands(
                     max_results,
                     upn,
                     directory,
+                    paging,
                 } => {
-                    let mut builder = filesystem.list_paths();
+                    let mut builder = filesystem
+                        .list_paths()
+                        .context(paging.context("continuation"));
                     args!(builder, recursive, directory, max_results, upn);
-                    output_stream_entries!(builder.into_stream(), paths);
+                    paging
+                        .output_stream(builder.into_stream(), |page| Ok(page.paths))
+                        .await?;
                 }
                 FileSystemSubCommands::Directory {
                     directory_name,
                     subcommand,
                 } => {
-                    let directory_client = filesystem.get_directory_client(directory_name);
+                    let directory_client = filesystem.get_directory_client(&directory_name);
                     match subcommand {
                         DirectorySubCommands::Create { properties } => {
                             let properties = properties.map(to_properties);
@@ -131,15 +166,41 @@ pub async fn datalake_commands(
                             max_results,
                             upn,
                             directory,
+                            paging,
                         } => {
-                            let mut builder = directory_client.list_paths();
+                            let mut builder = directory_client
+                                .list_paths()
+                                .context(paging.context("continuation"));
                             args!(builder, recursive, directory, max_results, upn);
-                            output_stream_entries!(builder.into_stream(), paths);
+                            paging
+                                .output_stream(builder.into_stream(), |page| Ok(page.paths))
+                                .await?;
                         }
                         DirectorySubCommands::Delete { recursive } => {
                             let result = directory_client.delete(recursive).await?;
                             println!("{result:#?}");
                         }
+                        DirectorySubCommands::GenerateSas {
+                            options,
+                            depth,
+                            identifier,
+                            sas_permissions,
+                        } => {
+                            let token = generate_directory_sas(
+                                blob_service_client,
+                                rest_client,
+                                &name,
+                                &directory_name,
+                                &options,
+                                depth,
+                                identifier.as_deref(),
+                                sas_permissions,
+                            )
+                            .await?;
+                            let mut url = directory_client.url()?;
+                            url.set_query(Some(&token));
+                            println!("{url}");
+                        }
                     }
                 }
             }
@@ -155,3 +216,50 @@ pub async fn datalake_commands(
     }
     Ok(())
 }
+
+/// Sign a directory SAS with the account key if one was specified, and with a
+/// user delegation key otherwise
+#[allow(clippy::too_many_arguments)]
+async fn generate_directory_sas(
+    blob_service_client: &BlobServiceClient,
+    rest_client: &RestClient,
+    file_system: &str,
+    directory: &str,
+    options: &SasOptions,
+    depth: Option<usize>,
+    identifier: Option<&str>,
+    sas_permissions: SasPermissions,
+) -> azure_core::Result<String> {
+    let validity = options.validity()?;
+    let (account, key) = if let Some((account, key)) = rest_client.shared_key().await {
+        (account, SasKey::Key(key))
+    } else {
+        let key = blob_service_client
+            .get_user_deligation_key(
+                validity.start.unwrap_or_else(OffsetDateTime::now_utc),
+                validity.expiry,
+            )
+            .await?
+            .user_deligation_key;
+        (
+            blob_service_client.account().to_string(),
+            SasKey::UserDelegationKey(key),
+        )
+    };
+    let depth = depth.unwrap_or_else(|| {
+        directory
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .count()
+    });
+    directory_sas(
+        &account,
+        file_system,
+        directory,
+        depth,
+        &key,
+        &BlobSasPermissions::try_from(sas_permissions)?,
+        &validity,
+        identifier,
+    )
+}
@@ -0,0 +1,311 @@
+use crate::{rest::RestClient, utils::output};
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    headers::Headers,
+    Method, Url,
+};
+use azure_storage::clients::ServiceType;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, iter::Peekable, num::NonZeroU32, str::CharIndices};
+
+const MAX_KEY_LENGTH: usize = 128;
+const MAX_VALUE_LENGTH: usize = 256;
+/// Tag key used to restrict an account-level query to a single container
+const CONTAINER_KEY: &str = "@container";
+
+/// Filter expression for the Find Blobs by Tags API, validated before it is
+/// sent to the service.
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/find-blobs-by-tags#constructing-a-search-expression>
+#[derive(Debug, Clone)]
+pub struct TagFilter {
+    expression: String,
+    has_container_condition: bool,
+}
+
+impl TagFilter {
+    pub(crate) fn has_container_condition(&self) -> bool {
+        self.has_container_condition
+    }
+}
+
+impl fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl std::str::FromStr for TagFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: s.char_indices().peekable(),
+        };
+        let mut has_container_condition = false;
+        loop {
+            parser.skip_whitespace();
+            let key = parser.key()?;
+            parser.skip_whitespace();
+            let op = parser.operator()?;
+            parser.skip_whitespace();
+            parser.value()?;
+
+            if key == CONTAINER_KEY {
+                if op != "=" {
+                    return Err(invalid(&format!("{CONTAINER_KEY} only supports `=`")));
+                }
+                has_container_condition = true;
+            }
+
+            parser.skip_whitespace();
+            if parser.chars.peek().is_none() {
+                break;
+            }
+            parser.keyword("AND")?;
+        }
+        Ok(Self {
+            expression: s.to_string(),
+            has_container_condition,
+        })
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::with_message(ErrorKind::DataConversion, || {
+        format!("invalid tag filter: {message}")
+    })
+}
+
+/// Characters permitted in tag keys and values
+fn is_tag_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, ' ' | '+' | '-' | '.' | '/' | ':' | '=' | '_')
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn quoted(&mut self, quote: char, max_length: usize) -> Result<String> {
+        let mut result = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, c)) if c == quote => break,
+                Some((_, c)) if is_tag_char(c) => result.push(c),
+                Some((i, c)) => {
+                    return Err(invalid(&format!("unsupported character `{c}` at {i}")));
+                }
+                None => return Err(invalid(&format!("missing closing `{quote}`"))),
+            }
+        }
+        if result.len() > max_length {
+            return Err(invalid(&format!(
+                "`{result}` is longer than {max_length} characters"
+            )));
+        }
+        Ok(result)
+    }
+
+    fn key(&mut self) -> Result<String> {
+        let key = match self.chars.peek() {
+            Some((_, '"')) => {
+                self.chars.next();
+                self.quoted('"', MAX_KEY_LENGTH)?
+            }
+            Some((_, '@')) => {
+                self.chars.next();
+                let mut key = "@".to_string();
+                while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                    key.push(c);
+                }
+                if key != CONTAINER_KEY {
+                    return Err(invalid(&format!("unknown key `{key}`")));
+                }
+                key
+            }
+            Some(_) => {
+                let mut key = String::new();
+                while let Some((_, c)) = self
+                    .chars
+                    .next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    key.push(c);
+                }
+                key
+            }
+            None => return Err(invalid("expected a tag key")),
+        };
+        if key.is_empty() {
+            let found = self
+                .chars
+                .peek()
+                .map_or(String::new(), |(i, c)| format!(" at {i}: `{c}`"));
+            return Err(invalid(&format!("expected a tag key{found}")));
+        }
+        if key.len() > MAX_KEY_LENGTH {
+            return Err(invalid(&format!(
+                "key `{key}` is longer than {MAX_KEY_LENGTH} characters"
+            )));
+        }
+        Ok(key)
+    }
+
+    fn operator(&mut self) -> Result<&'static str> {
+        let op = match self.chars.next() {
+            Some((_, '=')) => "=",
+            Some((_, '>')) if self.chars.next_if(|(_, c)| *c == '=').is_some() => ">=",
+            Some((_, '>')) => ">",
+            Some((_, '<')) if self.chars.next_if(|(_, c)| *c == '=').is_some() => "<=",
+            Some((_, '<')) => "<",
+            Some((i, c)) => {
+                return Err(invalid(&format!(
+                    "expected an operator at {i}, found `{c}`"
+                )))
+            }
+            None => return Err(invalid("expected an operator")),
+        };
+        Ok(op)
+    }
+
+    fn value(&mut self) -> Result<String> {
+        match self.chars.next() {
+            Some((_, '\'')) => self.quoted('\'', MAX_VALUE_LENGTH),
+            Some((i, c)) => Err(invalid(&format!(
+                "expected a single-quoted value at {i}, found `{c}`"
+            ))),
+            None => Err(invalid("expected a single-quoted value")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        let mut found = String::new();
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+            found.push(c);
+        }
+        if !found.eq_ignore_ascii_case(keyword) {
+            return Err(invalid(&format!("expected `{keyword}`, found `{found}`")));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FindBlobsByTagsBody {
+    next_marker: Option<String>,
+    blobs: Blobs,
+}
+
+#[derive(Debug, Deserialize)]
+struct Blobs {
+    #[serde(rename = "Blob", default)]
+    blobs: Vec<Blob>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Blob {
+    name: String,
+    container_name: String,
+    tags: Option<Tags>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Tags {
+    tag_set: TagSet,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagSet {
+    #[serde(rename = "Tag", default)]
+    tags: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Tag {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FoundBlob {
+    pub container: String,
+    pub name: String,
+    /// The tags on the blob that matched the filter
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Find the blobs matching the filter, following continuation markers until
+/// all of the results have been retrieved.
+///
+/// `url` is either the account URL or the container URL, with any
+/// `restype` query already applied.
+pub(crate) async fn find_blobs_by_tags(
+    rest_client: &RestClient,
+    url: Url,
+    filter: &TagFilter,
+    max_results: Option<NonZeroU32>,
+) -> Result<Vec<FoundBlob>> {
+    let mut found = vec![];
+    let mut marker: Option<String> = None;
+    loop {
+        let mut url = url.clone();
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("comp", "blobs")
+                .append_pair("where", &filter.expression);
+            if let Some(max_results) = max_results {
+                query.append_pair("maxresults", &max_results.to_string());
+            }
+            if let Some(marker) = &marker {
+                query.append_pair("marker", marker);
+            }
+        }
+
+        let body: FindBlobsByTagsBody = rest_client
+            .send(ServiceType::Blob, Method::Get, url, Headers::new(), None)
+            .await?
+            .xml()
+            .await?;
+
+        found.extend(body.blobs.blobs.into_iter().map(|blob| {
+            FoundBlob {
+                container: blob.container_name,
+                name: blob.name,
+                tags: blob
+                    .tags
+                    .map(|tags| {
+                        tags.tag_set
+                            .tags
+                            .into_iter()
+                            .map(|tag| (tag.key, tag.value))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        }));
+
+        marker = body.next_marker.filter(|marker| !marker.is_empty());
+        if marker.is_none() {
+            return Ok(found);
+        }
+    }
+}
+
+pub(crate) async fn output_blobs_by_tags(
+    rest_client: &RestClient,
+    url: Url,
+    filter: &TagFilter,
+    max_results: Option<NonZeroU32>,
+) -> Result<()> {
+    let found = find_blobs_by_tags(rest_client, url, filter, max_results).await?;
+    output(&found)
+}
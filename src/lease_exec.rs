@@ -0,0 +1,172 @@
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    prelude::LeaseDuration,
+    request_options::LeaseId,
+};
+use azure_storage_blobs::prelude::{BlobClient, ContainerClient};
+use std::{
+    process::{exit, ExitStatus},
+    time::Duration,
+};
+use tokio::{
+    process::Command,
+    signal::ctrl_c,
+    time::{interval_at, Instant},
+};
+use tracing::{debug, error};
+
+/// The container or blob that is leased while the command runs
+pub(crate) enum LeaseTarget<'a> {
+    Container(&'a ContainerClient),
+    Blob(&'a BlobClient),
+}
+
+impl LeaseTarget<'_> {
+    async fn acquire(&self, duration: LeaseDuration, proposed: Option<LeaseId>) -> Result<LeaseId> {
+        let lease_id = match self {
+            Self::Container(client) => {
+                let mut builder = client.acquire_lease(duration);
+                if let Some(proposed) = proposed {
+                    builder = builder.proposed_lease_id(proposed);
+                }
+                builder.await?.lease_id
+            }
+            Self::Blob(client) => {
+                let mut builder = client.acquire_lease(duration);
+                if let Some(proposed) = proposed {
+                    builder = builder.proposed_lease_id(proposed);
+                }
+                builder.await?.lease_id
+            }
+        };
+        Ok(lease_id)
+    }
+
+    async fn renew(&self, lease_id: LeaseId) -> Result<()> {
+        match self {
+            Self::Container(client) => {
+                client.container_lease_client(lease_id).renew().await?;
+            }
+            Self::Blob(client) => {
+                client.blob_lease_client(lease_id).renew().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn release(&self, lease_id: LeaseId) -> Result<()> {
+        match self {
+            Self::Container(client) => {
+                client.container_lease_client(lease_id).release().await?;
+            }
+            Self::Blob(client) => {
+                client.blob_lease_client(lease_id).release().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run a command while holding a lease
+///
+/// The lease is renewed in the background for as long as the command runs,
+/// and released when it exits or on Ctrl-C.  If the lease cannot be renewed,
+/// the command is killed, since it no longer holds the lease.
+///
+/// `azs` exits with the command's exit code, or 128 plus the signal that
+/// terminated it.
+#[derive(clap::Parser)]
+pub struct LeaseExec {
+    /// lease duration in seconds, between 15 and 60
+    ///
+    /// The lease is renewed at half this interval, and expires this long
+    /// after `azs` stops renewing it
+    #[clap(long, default_value_t = 60, value_parser = clap::value_parser!(u8).range(15..=60))]
+    duration: u8,
+    /// proposed lease id
+    #[clap(long)]
+    proposed_lease_id: Option<LeaseId>,
+    /// environment variable the lease id is exported to for the command
+    #[clap(long, default_value = "AZS_LEASE_ID")]
+    env_var: String,
+    /// command to run, and its arguments
+    #[clap(required = true, last = true)]
+    command: Vec<String>,
+}
+
+impl LeaseExec {
+    pub async fn execute(self, target: LeaseTarget<'_>) -> Result<()> {
+        let lease_id = target
+            .acquire(
+                LeaseDuration::Seconds(self.duration),
+                self.proposed_lease_id,
+            )
+            .await?;
+        debug!("acquired lease {lease_id}");
+
+        let result = self.run(&target, lease_id).await;
+
+        if let Err(err) = target.release(lease_id).await {
+            error!("unable to release lease {lease_id}: {err}");
+            result?;
+            return Err(err);
+        }
+        debug!("released lease {lease_id}");
+
+        let status = result?;
+        if !status.success() {
+            exit(exit_code(status));
+        }
+        Ok(())
+    }
+
+    async fn run(&self, target: &LeaseTarget<'_>, lease_id: LeaseId) -> Result<ExitStatus> {
+        let Some((program, args)) = self.command.split_first() else {
+            return Err(Error::message(ErrorKind::Other, "no command specified"));
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .env(&self.env_var, lease_id.to_string())
+            .spawn()?;
+
+        let period = Duration::from_secs(u64::from(self.duration) / 2);
+        let mut renewals = interval_at(Instant::now() + period, period);
+        loop {
+            tokio::select! {
+                status = child.wait() => {
+                    let status = status?;
+                    debug!("`{program}` exited: {status}");
+                    return Ok(status);
+                }
+                _ = renewals.tick() => {
+                    if let Err(err) = target.renew(lease_id).await {
+                        child.kill().await?;
+                        return Err(Error::full(
+                            ErrorKind::Other,
+                            err,
+                            format!("unable to renew lease {lease_id}, stopped `{program}`"),
+                        ));
+                    }
+                    debug!("renewed lease {lease_id}");
+                }
+                interrupt = ctrl_c() => {
+                    interrupt?;
+                    child.kill().await?;
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!("interrupted, stopped `{program}`")
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// The exit code of a command, or 128 plus the signal that terminated it, as
+/// shells report it
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
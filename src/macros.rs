@@ -70,17 +70,3 @@ macro_rules! output_stream_entries {
         serializer.end()?;
     }};
 }
-
-#[macro_export]
-macro_rules! output_stream_entries_debug {
-    ($stream:expr, $entry_name:expr) => {{
-        use futures::StreamExt;
-        let mut stream = $stream;
-        while let Some(item) = stream.next().await {
-            let item = item?;
-            for entry in paste::paste! { item. $entry_name } {
-                println!("{entry:#?}");
-            }
-        }
-    }};
-}
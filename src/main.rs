@@ -6,12 +6,18 @@
 #![allow(clippy::module_name_repetitions)]
 
 mod account;
+mod acl;
 mod blob;
 #[macro_use]
 mod macros;
 mod container;
 mod datalake;
+mod find_blobs_by_tags;
+mod lease_exec;
+mod paging;
 mod queue;
+mod rest;
+mod sas;
 mod tables;
 mod utils;
 
@@ -20,15 +26,17 @@ use self::{
     container::{container_commands, ContainerSubCommands},
     datalake::{datalake_commands, DatalakeSubCommands},
     queue::{queues_commands, QueuesSubCommands},
+    rest::RestClient,
+    sas::{sas_commands, SasSubCommands},
     tables::{table_commands, TableSubCommands},
 };
 use anyhow::{anyhow, Result};
-use azure_data_tables::clients::TableServiceClient;
+use azure_data_tables::clients::TableServiceClientBuilder;
 use azure_identity::create_default_credential;
-use azure_storage::prelude::StorageCredentials;
-use azure_storage_blobs::prelude::BlobServiceClient;
-use azure_storage_datalake::prelude::DataLakeClient;
-use azure_storage_queues::prelude::QueueServiceClient;
+use azure_storage::{prelude::StorageCredentials, CloudLocation};
+use azure_storage_blobs::prelude::ClientBuilder;
+use azure_storage_datalake::prelude::DataLakeClientBuilder;
+use azure_storage_queues::QueueServiceClientBuilder;
 use clap::{Command, CommandFactory, Parser, Subcommand};
 use std::{cmp::min, fmt::Write as _, io::stderr};
 use tracing::level_filters::LevelFilter;
@@ -46,6 +54,27 @@ struct Args {
     #[clap(env = "STORAGE_ACCOUNT", hide_env_values = true)]
     account: String,
 
+    /// Storage account access key, used instead of Azure AD authentication
+    ///
+    /// Required to sign SAS tokens with a shared key
+    #[clap(
+        long,
+        env = "STORAGE_ACCESS_KEY",
+        hide_env_values = true,
+        global = true
+    )]
+    access_key: Option<String>,
+
+    /// SAS token, used instead of Azure AD authentication
+    #[clap(
+        long,
+        env = "STORAGE_SAS_TOKEN",
+        hide_env_values = true,
+        global = true,
+        conflicts_with = "access_key"
+    )]
+    sas_token: Option<String>,
+
     #[command(subcommand)]
     subcommand: SubCommands,
 }
@@ -81,6 +110,11 @@ enum SubCommands {
         #[clap(subcommand)]
         subcommand: TableSubCommands,
     },
+    /// Work with shared access signatures
+    Sas {
+        #[clap(subcommand)]
+        subcommand: SasSubCommands,
+    },
     #[command(hide = true)]
     Readme,
 }
@@ -129,10 +163,25 @@ async fn main() -> Result<()> {
 
     let Args {
         account,
+        access_key,
+        sas_token,
         subcommand,
     } = Args::parse();
 
-    let storage_credentials = StorageCredentials::token_credential(create_default_credential()?);
+    let storage_credentials = if let Some(access_key) = access_key {
+        StorageCredentials::access_key(account.clone(), access_key)
+    } else if let Some(sas_token) = sas_token {
+        StorageCredentials::sas_token(sas_token)?
+    } else {
+        StorageCredentials::token_credential(create_default_credential()?)
+    };
+
+    let cloud_location = CloudLocation::Public { account };
+    let blob_service_client =
+        ClientBuilder::with_location(cloud_location.clone(), storage_credentials.clone())
+            .client_options(paging::client_options())
+            .blob_service_client();
+    let rest_client = RestClient::new(cloud_location.clone(), storage_credentials.clone());
 
     match subcommand {
         SubCommands::Readme => {
@@ -153,28 +202,49 @@ async fn main() -> Result<()> {
             print!("{readme}");
         }
         SubCommands::Account { subcommand } => {
-            let service_client = BlobServiceClient::new(&account, storage_credentials);
-            account_commands(&service_client, subcommand).await?;
+            account_commands(&blob_service_client, &rest_client, subcommand).await?;
         }
         SubCommands::Container {
             subcommand,
             container_name,
         } => {
-            let service_client = BlobServiceClient::new(&account, storage_credentials);
-            let container_client = service_client.container_client(container_name);
-            container_commands(&container_client, subcommand).await?;
+            let container_client = blob_service_client.container_client(container_name);
+            container_commands(&container_client, &rest_client, subcommand).await?;
         }
         SubCommands::Queues { subcommand } => {
-            let service_client = QueueServiceClient::new(&account, storage_credentials);
-            queues_commands(&service_client, subcommand).await?;
+            let service_client = QueueServiceClientBuilder::with_location(
+                cloud_location.clone(),
+                storage_credentials.clone(),
+            )
+            .client_options(paging::client_options())
+            .build();
+            queues_commands(&service_client, &rest_client, subcommand).await?;
         }
         SubCommands::Datalake { subcommand } => {
-            let service_client = DataLakeClient::new(&account, storage_credentials);
-            datalake_commands(&service_client, subcommand).await?;
+            let service_client = DataLakeClientBuilder::with_location(
+                cloud_location.clone(),
+                storage_credentials.clone(),
+            )
+            .client_options(paging::client_options())
+            .build();
+            datalake_commands(
+                &service_client,
+                &blob_service_client,
+                &rest_client,
+                subcommand,
+            )
+            .await?;
+        }
+        SubCommands::Sas { subcommand } => {
+            sas_commands(&rest_client, subcommand).await?;
         }
         SubCommands::Tables { subcommand } => {
-            let table_client = TableServiceClient::new(&account, storage_credentials);
-            table_commands(&table_client, subcommand).await?;
+            let table_client = TableServiceClientBuilder::with_location(
+                cloud_location.clone(),
+                storage_credentials.clone(),
+            )
+            .build();
+            table_commands(&table_client, &rest_client, subcommand).await?;
         }
     }
 
@@ -0,0 +1,323 @@
+//! Paging through listings with continuation markers
+//!
+//! The SDK's listings follow continuation markers until the last page, and
+//! most cannot start from a marker returned by an earlier listing.
+//! [`StartMarkerPolicy`] adds that marker to the first request of a listing
+//! when it is set in the request context.
+
+use crate::utils::output;
+use azure_core::{
+    error::{Error, ErrorKind, Result},
+    prelude::NextMarker,
+    ClientOptions, Context, Continuable, Policy, PolicyResult, Request,
+};
+use clap::Args;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use std::{cell::RefCell, iter, num::NonZeroUsize, sync::Arc};
+use tokio::{sync::mpsc, task};
+
+/// Entries listed ahead of those written to stdout
+const STREAM_BUFFER: usize = 1000;
+
+/// Resume a listing from a continuation marker and limit the number of pages
+#[derive(Debug, Args)]
+pub(crate) struct Paging {
+    /// continuation marker printed by a previous listing, to resume where it stopped
+    #[clap(long)]
+    marker: Option<String>,
+    /// stop after this many pages, printing the marker to continue from
+    #[clap(long)]
+    pages: Option<NonZeroUsize>,
+    /// wrap JSON output in an object with the `items` and the `next_marker` to
+    /// continue from, rather than printing the marker to stderr
+    #[clap(long)]
+    envelope: bool,
+}
+
+#[derive(Serialize)]
+struct Envelope<I, M> {
+    items: I,
+    next_marker: M,
+}
+
+/// A continuation returned by the service, as printed for `--marker`
+pub(crate) trait ToMarker {
+    fn to_marker(&self) -> String;
+}
+
+impl ToMarker for NextMarker {
+    fn to_marker(&self) -> String {
+        self.as_str().to_string()
+    }
+}
+
+/// Table queries continue from a partition key and an optional row key,
+/// which may contain any character, so they are printed as a JSON array
+impl ToMarker for (String, Option<String>) {
+    fn to_marker(&self) -> String {
+        json!([self.0, self.1]).to_string()
+    }
+}
+
+/// The marker a listing resumes from, added as the named query parameter to
+/// the first request by [`StartMarkerPolicy`]
+#[derive(Debug, Clone)]
+struct StartMarker {
+    parameter: &'static str,
+    value: String,
+}
+
+/// The pages of a listing, stopping after `--pages` pages
+pub(crate) struct Pages<S> {
+    stream: S,
+    remaining: Option<usize>,
+    next_marker: Option<String>,
+}
+
+impl<S, R> Pages<S>
+where
+    S: Stream<Item = Result<R>> + Unpin,
+    R: Continuable,
+    R::Continuation: ToMarker,
+{
+    pub(crate) async fn next(&mut self) -> Option<Result<R>> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        let page = self.stream.next().await?;
+        if let (Ok(page), Some(remaining)) = (&page, &mut self.remaining) {
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.next_marker = page.continuation().map(|marker| marker.to_marker());
+            }
+        }
+        Some(page)
+    }
+
+    /// The marker to continue from, when the listing stopped before its last
+    /// page
+    pub(crate) fn next_marker(&self) -> Option<&str> {
+        self.next_marker.as_deref()
+    }
+}
+
+/// An entry of a listing, or the marker to continue from once it stopped
+enum Listed<T> {
+    Entry(T),
+    Stopped(Option<String>),
+}
+
+/// Entries serialized as they are received from the listing
+///
+/// The marker the listing stopped at is only known after the last entry, so
+/// it is kept for the fields serialized after the entries.
+struct Received<T> {
+    receiver: RefCell<mpsc::Receiver<Listed<T>>>,
+    next_marker: RefCell<Option<String>>,
+}
+
+impl<T: Serialize> Serialize for Received<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut receiver = self.receiver.borrow_mut();
+        let entries = iter::from_fn(|| match receiver.blocking_recv()? {
+            Listed::Entry(entry) => Some(entry),
+            Listed::Stopped(next_marker) => {
+                *self.next_marker.borrow_mut() = next_marker;
+                None
+            }
+        });
+        serializer.collect_seq(entries)
+    }
+}
+
+impl Paging {
+    pub(crate) fn marker(&self) -> Option<&str> {
+        self.marker.as_deref()
+    }
+
+    /// Context for a listing that starts from the marker, if one was specified
+    ///
+    /// `parameter` is the query parameter the service reads the marker from.
+    pub(crate) fn context(&self, parameter: &'static str) -> Context {
+        let mut context = Context::new();
+        if let Some(value) = &self.marker {
+            context.insert(StartMarker {
+                parameter,
+                value: value.clone(),
+            });
+        }
+        context
+    }
+
+    /// The partition key and row key a table query resumes from
+    pub(crate) fn table_keys(&self) -> Result<Option<(String, Option<String>)>> {
+        self.marker
+            .as_deref()
+            .map(|marker| {
+                serde_json::from_str(marker).map_err(|e| {
+                    Error::full(
+                        ErrorKind::DataConversion,
+                        e,
+                        "table query markers are a JSON array of the partition key and row key",
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    pub(crate) fn pages<S>(&self, stream: S) -> Pages<S> {
+        Pages {
+            stream,
+            remaining: self.pages.map(NonZeroUsize::get),
+            next_marker: None,
+        }
+    }
+
+    /// Collect the entries of each page, stopping after `--pages` pages
+    ///
+    /// Returns the marker to continue from when the listing stopped before
+    /// its last page.
+    pub(crate) async fn collect<S, R, T, F>(
+        &self,
+        stream: S,
+        mut entries: F,
+    ) -> Result<(Vec<T>, Option<String>)>
+    where
+        S: Stream<Item = Result<R>> + Unpin,
+        R: Continuable,
+        R::Continuation: ToMarker,
+        F: FnMut(R) -> Result<Vec<T>>,
+    {
+        let mut pages = self.pages(stream);
+        let mut items = vec![];
+        while let Some(page) = pages.next().await {
+            items.extend(entries(page?)?);
+        }
+        Ok((items, pages.next_marker))
+    }
+
+    /// Output the entries as JSON, along with the marker to continue from
+    pub(crate) fn output<T: Serialize>(
+        &self,
+        items: &[T],
+        next_marker: Option<&str>,
+    ) -> Result<()> {
+        if self.envelope {
+            return output(&Envelope { items, next_marker });
+        }
+        output(items)?;
+        print_marker(next_marker);
+        Ok(())
+    }
+
+    /// Output the entries of each page as JSON as they are listed, stopping
+    /// after `--pages` pages, along with the marker to continue from
+    ///
+    /// The entries are written from a blocking task, so that the listing is
+    /// not held in memory even when `--envelope` writes the marker after
+    /// them.
+    pub(crate) async fn output_stream<S, R, T, F>(&self, stream: S, mut entries: F) -> Result<()>
+    where
+        S: Stream<Item = Result<R>> + Unpin,
+        R: Continuable,
+        R::Continuation: ToMarker,
+        F: FnMut(R) -> Result<Vec<T>>,
+        T: Serialize + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        let envelope = self.envelope;
+        let writer = task::spawn_blocking(move || {
+            let items = Received {
+                receiver: RefCell::new(receiver),
+                next_marker: RefCell::new(None),
+            };
+            if envelope {
+                output(&Envelope {
+                    items: &items,
+                    next_marker: &items.next_marker,
+                })
+            } else {
+                output(&items)
+            }
+        });
+
+        // a failed listing closes the channel, so that the entries listed
+        // before it are still written as valid JSON
+        let mut pages = self.pages(stream);
+        let listed: Result<()> = async {
+            while let Some(page) = pages.next().await {
+                for entry in entries(page?)? {
+                    if sender.send(Listed::Entry(entry)).await.is_err() {
+                        // the writer failed, which is reported below
+                        return Ok(());
+                    }
+                }
+            }
+            let _ = sender
+                .send(Listed::Stopped(pages.next_marker.clone()))
+                .await;
+            Ok(())
+        }
+        .await;
+        drop(sender);
+
+        writer
+            .await
+            .map_err(|e| Error::full(ErrorKind::Other, e, "failed to write the listing"))??;
+        listed?;
+        if !envelope {
+            print_marker(pages.next_marker());
+        }
+        Ok(())
+    }
+}
+
+/// Print the marker to continue a listing from to stderr, keeping stdout
+/// for the entries
+pub(crate) fn print_marker(next_marker: Option<&str>) {
+    if let Some(next_marker) = next_marker {
+        eprintln!("next marker: {next_marker}");
+    }
+}
+
+/// Client options that resume listings from the marker in their context
+pub(crate) fn client_options() -> ClientOptions {
+    let mut options = ClientOptions::default();
+    options
+        .per_call_policies_mut()
+        .push(Arc::new(StartMarkerPolicy));
+    options
+}
+
+/// Adds the marker from the request context to the first request of a
+/// listing, leaving later requests that carry the service's own continuation
+/// unchanged
+#[derive(Debug)]
+struct StartMarkerPolicy;
+
+#[async_trait::async_trait]
+impl Policy for StartMarkerPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        if let Some(marker) = ctx.get::<StartMarker>() {
+            let url = request.url_mut();
+            if !url.query_pairs().any(|(key, _)| key == marker.parameter) {
+                url.query_pairs_mut()
+                    .append_pair(marker.parameter, &marker.value);
+            }
+        }
+        let Some((policy, next)) = next.split_first() else {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "no policy after the start marker policy",
+            ));
+        };
+        policy.send(ctx, request, next).await
+    }
+}
@@ -1,11 +1,18 @@
 use crate::{
-    args,
-    utils::{parse_key_val, to_metadata},
+    acl::{get_acl, update_acl, Acl, PolicyChange},
+    paging::Paging,
+    rest::RestClient,
+    sas::{queue_sas, shared_key, QueueSasPermissions, SasOptions},
+    utils::{output, parse_key_val, to_metadata},
 };
+use azure_storage::clients::ServiceType;
 use azure_storage_queues::{PopReceipt, QueueClient, QueueServiceClient};
 use clap::Subcommand;
 use std::{num::NonZeroU32, time::Duration};
 
+/// Permissions that can be granted by a queue's stored access policy
+const QUEUE_PERMISSIONS: &str = "raup";
+
 #[derive(Subcommand)]
 pub enum QueuesSubCommands {
     GetProperties,
@@ -17,8 +24,11 @@ pub enum QueuesSubCommands {
         prefix: Option<String>,
         #[clap(long)]
         include_metadata: bool,
-        #[clap(long)]
+        /// max results to return per page
+        #[clap(long, visible_alias = "page-size")]
         max_results: Option<NonZeroU32>,
+        #[clap(flatten)]
+        paging: Paging,
     },
     Queue {
         /// queue name
@@ -30,6 +40,7 @@ pub enum QueuesSubCommands {
 
 pub async fn queues_commands(
     service_client: &QueueServiceClient,
+    rest_client: &RestClient,
     subcommand: QueuesSubCommands,
 ) -> azure_core::Result<()> {
     match subcommand {
@@ -41,19 +52,23 @@ pub async fn queues_commands(
             prefix,
             include_metadata,
             max_results,
+            paging,
         } => {
             let mut builder = service_client
                 .list_queues()
-                .include_metadata(include_metadata);
+                .include_metadata(include_metadata)
+                .context(paging.context("marker"));
             args!(builder, prefix, max_results);
-            output_stream_entries!(builder.into_stream(), queues);
+            paging
+                .output_stream(builder.into_stream(), |page| Ok(page.queues))
+                .await?;
         }
         QueuesSubCommands::Queue {
             queue_name,
             subcommand,
         } => {
             let queue_client = service_client.queue_client(&queue_name);
-            individual_queue_commands(&queue_client, subcommand).await?;
+            individual_queue_commands(&queue_client, rest_client, subcommand).await?;
         }
     }
     Ok(())
@@ -99,10 +114,30 @@ pub enum IndividualQueueSubCommands {
         /// Pop Receipt the message to be deleted (usually from the `GetMessages` response)
         pop_receipt: String,
     },
+    /// Get the stored access policies of the queue
+    GetAcl,
+    /// Change the stored access policies of the queue
+    SetAcl {
+        #[clap(subcommand)]
+        change: PolicyChange,
+    },
+    /// Generate a SAS URL for the queue, signed with the account access key
+    ///
+    /// Queues do not support user delegation SAS, so this requires `--access-key`
+    GenerateSas {
+        #[clap(flatten)]
+        options: SasOptions,
+        /// Stored access policy on the queue
+        #[clap(long)]
+        identifier: Option<String>,
+        #[clap(flatten)]
+        permissions: QueueSasPermissions,
+    },
 }
 
 pub async fn individual_queue_commands(
     queue_client: &QueueClient,
+    rest_client: &RestClient,
     subcommand: IndividualQueueSubCommands,
 ) -> azure_core::Result<()> {
     match subcommand {
@@ -160,6 +195,39 @@ pub async fn individual_queue_commands(
             let result = builder.await?;
             println!("{result:#?}");
         }
+        IndividualQueueSubCommands::GetAcl => {
+            let (_, policies) =
+                get_acl(rest_client, ServiceType::Queue, queue_client.url()?).await?;
+            output(&Acl::new(None, policies))?;
+        }
+        IndividualQueueSubCommands::SetAcl { change } => {
+            update_acl(
+                rest_client,
+                ServiceType::Queue,
+                queue_client.url()?,
+                change,
+                QUEUE_PERMISSIONS,
+            )
+            .await?;
+        }
+        IndividualQueueSubCommands::GenerateSas {
+            options,
+            identifier,
+            permissions,
+        } => {
+            let (account, key) = shared_key(rest_client, "queue").await?;
+            let token = queue_sas(
+                &account,
+                queue_client.queue_name(),
+                &key,
+                &permissions,
+                &options.validity()?,
+                identifier.as_deref(),
+            )?;
+            let mut url = queue_client.url()?;
+            url.set_query(Some(&token));
+            println!("{url}");
+        }
     }
     Ok(())
 }
@@ -0,0 +1,149 @@
+use azure_core::{
+    auth::Secret,
+    date,
+    error::{Error, ErrorKind},
+    headers::{Headers, CONTENT_LENGTH, MS_DATE},
+    Body, ClientOptions, Context, Method, Pipeline, Policy, PolicyResult, Request, Response,
+    StatusCode, TransportOptions, Url,
+};
+use azure_storage::{
+    clients::{finalize_request, new_pipeline_from_options, ServiceType},
+    CloudLocation, StorageCredentials, StorageCredentialsInner,
+};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+/// Sends requests for storage REST operations that are not exposed by the
+/// Azure SDK crates, using the same credentials as the SDK clients.
+#[derive(Debug, Clone)]
+pub struct RestClient {
+    cloud_location: CloudLocation,
+    credentials: StorageCredentials,
+    pipeline: Pipeline,
+    signing_pipeline: Pipeline,
+}
+
+impl RestClient {
+    pub fn new(cloud_location: CloudLocation, credentials: StorageCredentials) -> Self {
+        let pipeline = new_pipeline_from_options(ClientOptions::default(), credentials.clone());
+        let signing_pipeline = new_pipeline_from_options(
+            ClientOptions::new(TransportOptions::new_custom_policy(Arc::new(CapturePolicy))),
+            credentials.clone(),
+        );
+        Self {
+            cloud_location,
+            credentials,
+            pipeline,
+            signing_pipeline,
+        }
+    }
+
+    /// The endpoint of a storage service, such as `table` or `file`
+    ///
+    /// The SDK only knows the endpoints of the services it has clients for,
+    /// so for the public and sovereign clouds the service in the host name of
+    /// the blob endpoint is replaced.  Custom endpoints and the emulator serve
+    /// each service from the same URL.
+    pub fn service_url(&self, service: &str) -> azure_core::Result<Url> {
+        let mut url = self.cloud_location.url(ServiceType::Blob)?;
+        if let CloudLocation::Public { account } | CloudLocation::China { account } =
+            &self.cloud_location
+        {
+            let host = url
+                .host_str()
+                .and_then(|host| host.strip_prefix(&format!("{account}.blob.")))
+                .map(|suffix| format!("{account}.{service}.{suffix}"));
+            if let Some(host) = host {
+                url.set_host(Some(&host))?;
+            }
+        }
+        Ok(url)
+    }
+
+    /// The credentials used to authorize requests
+    pub fn credentials(&self) -> &StorageCredentials {
+        &self.credentials
+    }
+
+    /// The account name and key, when authenticating with a shared key
+    pub async fn shared_key(&self) -> Option<(String, Secret)> {
+        match &*self.credentials.0.read().await {
+            StorageCredentialsInner::Key(account, key) => Some((account.clone(), key.clone())),
+            _ => None,
+        }
+    }
+
+    pub async fn send(
+        &self,
+        service_type: ServiceType,
+        method: Method,
+        url: Url,
+        headers: Headers,
+        body: Option<Body>,
+    ) -> azure_core::Result<Response> {
+        let mut request = finalize_request(url, method, headers, body)?;
+        let mut context = Context::new();
+        context.insert(service_type);
+        self.pipeline.send(&context, &mut request).await
+    }
+
+    /// Authorize a request without sending it, such as the sub-requests of a
+    /// batch, which are each authorized separately.
+    ///
+    /// The request is passed through the same policies as requests that are
+    /// sent, with the transport replaced by one that captures the request.
+    pub async fn sign(
+        &self,
+        service_type: ServiceType,
+        method: Method,
+        url: Url,
+        headers: Headers,
+    ) -> azure_core::Result<Request> {
+        let mut request = Request::new(url, method);
+        for (key, value) in headers {
+            request.insert_header(key, value);
+        }
+        request.insert_header(CONTENT_LENGTH, "0");
+        request.insert_header(MS_DATE, date::to_rfc1123(&OffsetDateTime::now_utc()));
+
+        let captured = CapturedRequest::default();
+        let mut context = Context::new();
+        context.insert(service_type);
+        context.insert(captured.clone());
+        self.signing_pipeline.send(&context, &mut request).await?;
+
+        let request = captured
+            .0
+            .lock()
+            .ok()
+            .and_then(|mut request| request.take());
+        request.ok_or_else(|| Error::message(ErrorKind::Other, "request was not authorized"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct CapturedRequest(Arc<Mutex<Option<Request>>>);
+
+#[derive(Debug)]
+struct CapturePolicy;
+
+#[async_trait::async_trait]
+impl Policy for CapturePolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        _next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        if let Some(captured) = ctx.get::<CapturedRequest>() {
+            if let Ok(mut captured) = captured.0.lock() {
+                *captured = Some(request.clone());
+            }
+        }
+        Ok(Response::new(
+            StatusCode::Ok,
+            Headers::new(),
+            Box::pin(futures::stream::empty()),
+        ))
+    }
+}
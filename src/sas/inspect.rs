@@ -0,0 +1,689 @@
+//! Offline inspection of SAS URLs and tokens
+//!
+//! The checks cover the mistakes that most often cause a SAS to be rejected,
+//! such as an expired time window, permissions or resource types that do not
+//! match the URL, or a signature made with a different key.
+
+use super::{sign, BLOB_PERMISSIONS};
+use crate::{rest::RestClient, utils::output};
+use azure_core::{
+    auth::Secret,
+    error::{Error, ErrorKind, Result},
+    Url,
+};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+use time::{
+    format_description::{self, well_known::Rfc3339},
+    Date, OffsetDateTime, PrimitiveDateTime,
+};
+use url::form_urlencoded;
+
+const ACCOUNT_PERMISSIONS: &str = "rwdxylacuptfi";
+const CONTAINER_PERMISSIONS: &str = "racwdxyltfmeopi";
+const DIRECTORY_PERMISSIONS: &str = "racwdlmeop";
+const QUEUE_PERMISSIONS: &str = "raup";
+const TABLE_PERMISSIONS: &str = "raud";
+
+/// Decode a SAS URL or token and check it for common problems
+///
+/// When `--access-key` is specified, the signature is recomputed and checked.
+/// SAS signed with a user delegation key cannot be checked offline.
+#[derive(clap::Parser)]
+pub struct Inspect {
+    /// SAS URL, or only the SAS token
+    sas: String,
+    /// Permissions the SAS is expected to grant, such as `rl`
+    #[clap(long)]
+    require: Option<String>,
+    /// Output the report as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Account,
+    Blob,
+    UserDelegation,
+    Queue,
+    Table,
+    File,
+    Unknown,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Account => "account SAS",
+            Self::Blob => "blob service SAS",
+            Self::UserDelegation => "user delegation SAS",
+            Self::Queue => "queue service SAS",
+            Self::Table => "table service SAS",
+            Self::File => "file service SAS",
+            Self::Unknown => "unknown SAS",
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Parameter {
+    name: String,
+    value: String,
+    description: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Signature {
+    Valid,
+    Invalid,
+    NotChecked { reason: String },
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Valid => f.write_str("valid"),
+            Self::Invalid => f.write_str("INVALID"),
+            Self::NotChecked { reason } => write!(f, "not checked ({reason})"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    parameters: Vec<Parameter>,
+    permissions: Vec<&'static str>,
+    problems: Vec<String>,
+    signature: Signature,
+}
+
+/// The parts of the SAS and the URL it was used with
+struct Sas {
+    params: BTreeMap<String, String>,
+    account: Option<String>,
+    service: Option<&'static str>,
+    /// Decoded path segments of the URL
+    segments: Vec<String>,
+    kind: Kind,
+}
+
+impl Sas {
+    fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let (query, account, service, segments) = if input.contains("://") {
+            let url = Url::parse(input)?;
+            let mut labels = url.host_str().unwrap_or_default().split('.');
+            let account = labels.next().map(ToString::to_string);
+            let service = labels.next().and_then(|label| match label {
+                "blob" | "dfs" => Some("blob"),
+                "queue" => Some("queue"),
+                "table" => Some("table"),
+                "file" => Some("file"),
+                _ => None,
+            });
+            let segments = url
+                .path_segments()
+                .into_iter()
+                .flatten()
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+                .collect();
+            let query = url.query().unwrap_or_default().to_string();
+            (query, account, service, segments)
+        } else {
+            (
+                input.trim_start_matches('?').to_string(),
+                None,
+                None,
+                vec![],
+            )
+        };
+
+        let params: BTreeMap<String, String> = form_urlencoded::parse(query.as_bytes())
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        if !params.contains_key("sig") {
+            return Err(Error::message(
+                ErrorKind::DataConversion,
+                "no SAS signature (`sig`) was found",
+            ));
+        }
+
+        let kind = if params.contains_key("ss") {
+            Kind::Account
+        } else if params.contains_key("skoid") {
+            Kind::UserDelegation
+        } else if params.contains_key("tn") {
+            Kind::Table
+        } else if let Some(resource) = params.get("sr") {
+            if matches!(resource.as_str(), "s" | "f") {
+                Kind::File
+            } else {
+                Kind::Blob
+            }
+        } else if service == Some("queue") {
+            Kind::Queue
+        } else {
+            Kind::Unknown
+        };
+
+        Ok(Self {
+            params,
+            account,
+            service,
+            segments,
+            kind,
+        })
+    }
+
+    fn get(&self, name: &str) -> &str {
+        self.params.get(name).map_or("", String::as_str)
+    }
+
+    fn has_url(&self) -> bool {
+        self.account.is_some()
+    }
+
+    /// Permission flags that apply to this kind of SAS, in the required order
+    fn allowed_permissions(&self) -> Option<&'static str> {
+        match self.kind {
+            Kind::Account => Some(ACCOUNT_PERMISSIONS),
+            Kind::Blob | Kind::UserDelegation => Some(match self.get("sr") {
+                "c" => CONTAINER_PERMISSIONS,
+                "d" => DIRECTORY_PERMISSIONS,
+                _ => BLOB_PERMISSIONS,
+            }),
+            Kind::Queue => Some(QUEUE_PERMISSIONS),
+            Kind::Table => Some(TABLE_PERMISSIONS),
+            Kind::File | Kind::Unknown => None,
+        }
+    }
+
+    fn permission_name(&self, flag: char) -> &'static str {
+        match (flag, self.kind) {
+            ('r', _) => "read",
+            ('a', _) => "add",
+            ('c', _) => "create",
+            ('w', _) => "write",
+            ('d', _) => "delete",
+            ('x', _) => "delete version",
+            ('y', _) => "permanent delete",
+            ('l', _) => "list",
+            ('t', _) => "tags",
+            ('f', _) => "filter by tags",
+            ('m', _) => "move",
+            ('e', _) => "execute",
+            ('o', _) => "ownership",
+            ('p', Kind::Blob | Kind::UserDelegation) => "permissions",
+            ('p', _) => "process",
+            ('u', _) => "update",
+            ('i', _) => "set immutability policy",
+            _ => "unknown",
+        }
+    }
+
+    fn check(&self, require: Option<&str>, now: OffsetDateTime) -> Vec<String> {
+        let mut problems = vec![];
+        self.check_required(&mut problems);
+        self.check_window(&mut problems, now);
+        self.check_permissions(&mut problems, require);
+        self.check_resource(&mut problems);
+        problems
+    }
+
+    fn check_required(&self, problems: &mut Vec<String>) {
+        let mut required = vec!["sv"];
+        if !self.params.contains_key("si") {
+            required.extend(["sp", "se"]);
+        }
+        match self.kind {
+            Kind::Account => required.extend(["ss", "srt"]),
+            Kind::UserDelegation => required.extend(["sr", "sktid", "skt", "ske", "sks", "skv"]),
+            Kind::Blob | Kind::File => required.push("sr"),
+            Kind::Queue | Kind::Table | Kind::Unknown => {}
+        }
+        for name in required {
+            if self.get(name).is_empty() {
+                problems.push(format!("missing required parameter `{name}`"));
+            }
+        }
+        if self.kind == Kind::Unknown {
+            problems.push(
+                "the kind of SAS cannot be determined, specify the full URL it is used with"
+                    .to_string(),
+            );
+        }
+        if self.get("spr") == "http" {
+            problems.push("`spr` must be `https` or `https,http`".to_string());
+        }
+    }
+
+    fn check_window(&self, problems: &mut Vec<String>, now: OffsetDateTime) {
+        let mut time = |name: &str| {
+            let value = self.params.get(name)?;
+            let parsed = parse_sas_time(value);
+            if parsed.is_none() {
+                problems.push(format!("`{name}` is not a valid time: {value}"));
+            }
+            parsed
+        };
+        let start = time("st");
+        let expiry = time("se");
+        let key_start = time("skt");
+        let key_expiry = time("ske");
+
+        if let Some(start) = start {
+            if start > now {
+                problems.push(format!("not valid yet, the SAS starts at {}", show(start)));
+            }
+        }
+        if let Some(expiry) = expiry {
+            if expiry <= now {
+                problems.push(format!("expired at {}", show(expiry)));
+            }
+            if start.is_some_and(|start| start >= expiry) {
+                problems.push("the start time is not before the expiry".to_string());
+            }
+        }
+        if let Some(key_expiry) = key_expiry {
+            if key_expiry <= now {
+                problems.push(format!(
+                    "the user delegation key expired at {}",
+                    show(key_expiry)
+                ));
+            }
+            if expiry.is_some_and(|expiry| expiry > key_expiry) {
+                problems.push("the SAS expires after its user delegation key".to_string());
+            }
+        }
+        if key_start.is_some_and(|key_start| key_start > now) {
+            problems.push("the user delegation key is not valid yet".to_string());
+        }
+    }
+
+    fn check_permissions(&self, problems: &mut Vec<String>, require: Option<&str>) {
+        let granted = self.get("sp");
+        if let Some(allowed) = self.allowed_permissions() {
+            for flag in granted.chars().filter(|flag| !allowed.contains(*flag)) {
+                problems.push(format!(
+                    "permission `{flag}` does not apply to a {}",
+                    self.kind
+                ));
+            }
+            let ordered: String = allowed
+                .chars()
+                .filter(|flag| granted.contains(*flag))
+                .collect();
+            if ordered.len() == granted.len() && ordered != granted {
+                problems.push(format!(
+                    "permissions must be in the order `{allowed}`, such as `{ordered}`"
+                ));
+            }
+        }
+        for flag in require.unwrap_or_default().chars() {
+            if !granted.contains(flag) {
+                problems.push(format!(
+                    "missing required permission `{flag}` ({})",
+                    self.permission_name(flag)
+                ));
+            }
+        }
+    }
+
+    fn check_resource(&self, problems: &mut Vec<String>) {
+        let expected_service = match self.kind {
+            Kind::Blob | Kind::UserDelegation => Some("blob"),
+            Kind::Queue => Some("queue"),
+            Kind::Table => Some("table"),
+            Kind::File => Some("file"),
+            Kind::Account | Kind::Unknown => None,
+        };
+        if let (Some(expected), Some(service)) = (expected_service, self.service) {
+            if expected != service {
+                problems.push(format!(
+                    "a {} cannot be used with the {service} service",
+                    self.kind
+                ));
+            }
+        }
+
+        match self.kind {
+            Kind::Account => self.check_account_resource(problems),
+            Kind::Blob | Kind::UserDelegation => self.check_blob_resource(problems),
+            Kind::Table => {
+                let table = self.segments.first().map(|segment| {
+                    segment
+                        .split_once('(')
+                        .map_or(segment.as_str(), |(name, _)| name)
+                });
+                if let Some(table) = table {
+                    if !table.eq_ignore_ascii_case(self.get("tn")) {
+                        problems.push(format!(
+                            "the SAS is for table `{}` but the URL refers to `{table}`",
+                            self.get("tn")
+                        ));
+                    }
+                }
+            }
+            Kind::Queue | Kind::File | Kind::Unknown => {}
+        }
+    }
+
+    fn check_account_resource(&self, problems: &mut Vec<String>) {
+        let services = self.get("ss");
+        if let Some(service) = self.service {
+            let flag = service.chars().next().unwrap_or_default();
+            if !services.contains(flag) {
+                problems.push(format!(
+                    "`ss={services}` does not include the {service} service (`{flag}`)"
+                ));
+            }
+        }
+        if !self.has_url() {
+            return;
+        }
+        let (level, name) = match (self.service, self.segments.as_slice()) {
+            (_, []) => ('s', "service"),
+            (Some("table"), [table]) if table.starts_with("Tables") => ('c', "container"),
+            (Some("table"), _) | (_, [_, _, ..]) => ('o', "object"),
+            (_, [_]) => ('c', "container"),
+        };
+        let resource_types = self.get("srt");
+        if !resource_types.contains(level) {
+            problems.push(format!(
+                "`srt={resource_types}` does not include the {name} resource type (`{level}`) used by the URL"
+            ));
+        }
+    }
+
+    fn check_blob_resource(&self, problems: &mut Vec<String>) {
+        let resource = self.get("sr");
+        if !matches!(resource, "c" | "b" | "bv" | "bs" | "d") {
+            problems.push(format!("unknown signed resource `sr={resource}`"));
+            return;
+        }
+        if resource == "d" {
+            match self.get("sdd").parse::<usize>() {
+                Ok(depth) if self.has_url() && depth + 1 > self.segments.len() => {
+                    problems.push(format!(
+                        "`sdd={depth}` is deeper than the URL's path within the file system"
+                    ));
+                }
+                Ok(_) => {}
+                Err(_) => problems.push("`sr=d` requires a valid `sdd`".to_string()),
+            }
+        }
+        if self.has_url() {
+            match self.segments.len() {
+                0 => problems.push("the URL does not refer to a container".to_string()),
+                1 if resource != "c" => problems.push(format!(
+                    "`sr={resource}` is for a blob or directory, but the URL refers to a container"
+                )),
+                _ => {}
+            }
+        }
+    }
+
+    /// The canonicalized resource used by service SAS signatures
+    fn canonicalized_resource(&self, account: &str) -> Option<String> {
+        match self.kind {
+            Kind::Queue => Some(format!("/queue/{account}/{}", self.segments.first()?)),
+            Kind::Table => Some(format!(
+                "/table/{account}/{}",
+                self.get("tn").to_lowercase()
+            )),
+            Kind::Blob => {
+                let count = match self.get("sr") {
+                    "c" => 1,
+                    "d" => self.get("sdd").parse::<usize>().ok()? + 1,
+                    _ => self.segments.len(),
+                };
+                let path = self.segments.get(..count)?.join("/");
+                Some(format!("/blob/{account}/{path}"))
+            }
+            _ => None,
+        }
+    }
+
+    fn string_to_sign(&self, account: &str) -> std::result::Result<String, String> {
+        let version = self.get("sv");
+        let fields: Vec<&str> = match self.kind {
+            Kind::Account if version >= "2020-12-06" => {
+                vec!["sp", "ss", "srt", "st", "se", "sip", "spr", "sv", "ses", ""]
+            }
+            Kind::Account => vec!["sp", "ss", "srt", "st", "se", "sip", "spr", "sv", ""],
+            Kind::Blob if version >= "2020-12-06" => vec![
+                "sp", "st", "se", "", "si", "sip", "spr", "sv", "sr", "snapshot", "ses", "rscc",
+                "rscd", "rsce", "rscl", "rsct",
+            ],
+            Kind::Blob if version >= "2018-11-09" => vec![
+                "sp", "st", "se", "", "si", "sip", "spr", "sv", "sr", "snapshot", "rscc", "rscd",
+                "rsce", "rscl", "rsct",
+            ],
+            Kind::Queue if version >= "2015-04-05" => {
+                vec!["sp", "st", "se", "", "si", "sip", "spr", "sv"]
+            }
+            Kind::Table if version >= "2015-04-05" => vec![
+                "sp", "st", "se", "", "si", "sip", "spr", "sv", "spk", "srk", "epk", "erk",
+            ],
+            Kind::UserDelegation => {
+                return Err(
+                    "signed with a user delegation key, which is not available offline".into(),
+                )
+            }
+            Kind::File | Kind::Unknown => {
+                return Err(format!("checking a {} is not supported", self.kind))
+            }
+            _ => return Err(format!("version {version} is not supported")),
+        };
+
+        if self.kind == Kind::Account {
+            let mut values = vec![account];
+            values.extend(fields.iter().map(|name| self.get(name)));
+            return Ok(values.join("\n"));
+        }
+
+        let resource = self
+            .canonicalized_resource(account)
+            .ok_or_else(|| "the full URL is required to check a service SAS".to_string())?;
+        // The canonicalized resource is the only unnamed field
+        Ok(fields
+            .iter()
+            .map(|name| {
+                if name.is_empty() {
+                    resource.as_str()
+                } else {
+                    self.get(name)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn check_signature(
+        &self,
+        key: Option<(String, Secret)>,
+        problems: &mut Vec<String>,
+    ) -> Signature {
+        let Some((key_account, key)) = key else {
+            return Signature::NotChecked {
+                reason: "no --access-key was specified".to_string(),
+            };
+        };
+        let account = match &self.account {
+            Some(account) if !account.eq_ignore_ascii_case(&key_account) => {
+                problems.push(format!(
+                    "the access key is for account `{key_account}`, but the URL is for `{account}`"
+                ));
+                account.clone()
+            }
+            _ => key_account,
+        };
+        match self.string_to_sign(&account) {
+            Ok(string_to_sign) => match sign(&string_to_sign, &key) {
+                Ok(signature) if signature == self.get("sig") => Signature::Valid,
+                Ok(_) => {
+                    problems.push("the signature does not match the access key".to_string());
+                    Signature::Invalid
+                }
+                Err(err) => Signature::NotChecked {
+                    reason: err.to_string(),
+                },
+            },
+            Err(reason) => Signature::NotChecked { reason },
+        }
+    }
+}
+
+fn describe(name: &str) -> &'static str {
+    match name {
+        "sv" => "signed version",
+        "ss" => "signed services",
+        "srt" => "signed resource types",
+        "sp" => "signed permissions",
+        "st" => "start time",
+        "se" => "expiry time",
+        "sip" => "allowed IP addresses",
+        "spr" => "allowed protocols",
+        "sr" => "signed resource",
+        "si" => "stored access policy",
+        "sdd" => "directory depth",
+        "ses" => "encryption scope",
+        "tn" => "table name",
+        "spk" => "start partition key",
+        "srk" => "start row key",
+        "epk" => "end partition key",
+        "erk" => "end row key",
+        "skoid" => "user delegation key object id",
+        "sktid" => "user delegation key tenant id",
+        "skt" => "user delegation key start time",
+        "ske" => "user delegation key expiry time",
+        "sks" => "user delegation key service",
+        "skv" => "user delegation key version",
+        "saoid" => "authorized object id",
+        "suoid" => "unauthorized object id",
+        "scid" => "correlation id",
+        "rscc" => "Cache-Control response header",
+        "rscd" => "Content-Disposition response header",
+        "rsce" => "Content-Encoding response header",
+        "rscl" => "Content-Language response header",
+        "rsct" => "Content-Type response header",
+        "sig" => "signature",
+        _ => "not a SAS parameter",
+    }
+}
+
+fn show(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Parse a SAS time, which may omit the time of day or the seconds
+fn parse_sas_time(value: &str) -> Option<OffsetDateTime> {
+    if let Ok(time) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Some(time);
+    }
+    if let Ok(format) = format_description::parse("[year]-[month]-[day]T[hour]:[minute]Z") {
+        if let Ok(time) = PrimitiveDateTime::parse(value, &format) {
+            return Some(time.assume_utc());
+        }
+    }
+    let format = format_description::parse("[year]-[month]-[day]").ok()?;
+    Date::parse(value, &format)
+        .ok()
+        .map(|date| date.midnight().assume_utc())
+}
+
+impl Inspect {
+    pub async fn execute(self, rest_client: &RestClient) -> Result<()> {
+        let sas = Sas::parse(&self.sas)?;
+        let mut problems = sas.check(self.require.as_deref(), OffsetDateTime::now_utc());
+        let signature = sas.check_signature(rest_client.shared_key().await, &mut problems);
+
+        let report = Report {
+            kind: sas.kind,
+            account: sas.account.clone(),
+            service: sas.service,
+            path: sas.has_url().then(|| sas.segments.join("/")),
+            parameters: sas
+                .params
+                .iter()
+                .map(|(name, value)| Parameter {
+                    name: name.clone(),
+                    value: value.clone(),
+                    description: describe(name),
+                })
+                .collect(),
+            permissions: sas
+                .get("sp")
+                .chars()
+                .map(|flag| sas.permission_name(flag))
+                .collect(),
+            problems,
+            signature,
+        };
+
+        if self.json {
+            output(&report)?;
+        } else {
+            print_report(&report);
+        }
+
+        if !report.problems.is_empty() {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!("found {} problems with the SAS", report.problems.len())
+            }));
+        }
+        Ok(())
+    }
+}
+
+fn print_report(report: &Report) {
+    println!("Kind:        {}", report.kind);
+    if let Some(account) = &report.account {
+        println!("Account:     {account}");
+    }
+    if let Some(service) = report.service {
+        println!("Service:     {service}");
+    }
+    if let Some(path) = &report.path {
+        println!("Path:        /{path}");
+    }
+    if !report.permissions.is_empty() {
+        println!("Permissions: {}", report.permissions.join(", "));
+    }
+    println!("Signature:   {}", report.signature);
+    println!();
+    println!("Parameters:");
+    let width = report
+        .parameters
+        .iter()
+        .map(|parameter| parameter.name.len())
+        .max()
+        .unwrap_or_default();
+    for parameter in &report.parameters {
+        println!(
+            "  {:width$}  {}  ({})",
+            parameter.name, parameter.value, parameter.description
+        );
+    }
+    println!();
+    if report.problems.is_empty() {
+        println!("No problems found");
+    } else {
+        println!("Problems:");
+        for problem in &report.problems {
+            println!("  - {problem}");
+        }
+    }
+}
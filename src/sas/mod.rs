@@ -0,0 +1,609 @@
+//! Shared access signatures that are signed locally, for the cases the Azure
+//! SDK crates do not support.
+
+mod inspect;
+
+use self::inspect::Inspect;
+use crate::{
+    rest::RestClient,
+    utils::{parse_time, Protocol, TimeFormat},
+};
+use azure_core::{
+    auth::Secret,
+    error::{Error, ErrorKind, Result},
+    hmac::hmac_sha256,
+};
+use azure_storage::shared_access_signature::service_sas::{BlobSasPermissions, SasKey};
+use clap::{Args, Subcommand};
+use std::{fmt, str::FromStr};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
+use url::form_urlencoded;
+
+#[derive(Subcommand)]
+pub enum SasSubCommands {
+    /// Decode a SAS URL or token and check it for common problems
+    Inspect(Inspect),
+}
+
+pub async fn sas_commands(rest_client: &RestClient, subcommand: SasSubCommands) -> Result<()> {
+    match subcommand {
+        SasSubCommands::Inspect(inspect) => inspect.execute(rest_client).await,
+    }
+}
+
+/// Service version used to sign and interpret the tokens
+pub(crate) const SAS_VERSION: &str = "2022-11-02";
+
+/// Validity and network restrictions common to each kind of SAS
+#[derive(Debug, Args)]
+pub(crate) struct SasOptions {
+    /// Expiration
+    expiry: String,
+    /// Start time
+    #[clap(long)]
+    start: Option<String>,
+    /// Format used for the start and expiry times
+    #[clap(long, value_enum, default_value_t = TimeFormat::Offset)]
+    time_format: TimeFormat,
+    /// IP address or range of addresses from which to accept requests, such as `168.1.5.60-168.1.5.70`
+    #[clap(long)]
+    ip: Option<String>,
+    #[clap(long)]
+    protocol: Option<Protocol>,
+}
+
+/// `SasOptions` with the times parsed
+pub(crate) struct SasValidity {
+    pub start: Option<OffsetDateTime>,
+    pub expiry: OffsetDateTime,
+    pub ip: Option<String>,
+    pub protocol: Option<&'static str>,
+}
+
+impl SasOptions {
+    pub(crate) fn validity(&self) -> Result<SasValidity> {
+        Ok(SasValidity {
+            start: self
+                .start
+                .as_deref()
+                .map(|start| parse_time(start, self.time_format))
+                .transpose()?,
+            expiry: parse_time(&self.expiry, self.time_format)?,
+            ip: self.ip.clone(),
+            protocol: self.protocol.as_ref().map(|protocol| match protocol {
+                Protocol::Https => "https",
+                Protocol::HttpHttps => "https,http",
+            }),
+        })
+    }
+}
+
+impl SasValidity {
+    pub(crate) fn start(&self) -> Result<String> {
+        self.start
+            .map(format_time)
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub(crate) fn expiry(&self) -> Result<String> {
+        format_time(self.expiry)
+    }
+
+    pub(crate) fn ip(&self) -> &str {
+        self.ip.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn protocol(&self) -> &str {
+        self.protocol.unwrap_or_default()
+    }
+
+    /// Append the optional start, IP and protocol query parameters
+    pub(crate) fn append_to(&self, form: &mut form_urlencoded::Serializer<String>) -> Result<()> {
+        if self.start.is_some() {
+            form.append_pair("st", &self.start()?);
+        }
+        if let Some(ip) = &self.ip {
+            form.append_pair("sip", ip);
+        }
+        if let Some(protocol) = self.protocol {
+            form.append_pair("spr", protocol);
+        }
+        Ok(())
+    }
+}
+
+/// Format a time as expected in SAS tokens, which is UTC truncated to whole
+/// seconds as the service does when validating the signature.
+pub(crate) fn format_time(time: OffsetDateTime) -> Result<String> {
+    time.to_offset(UtcOffset::UTC)
+        .replace_nanosecond(0)
+        .map_err(|e| Error::new(ErrorKind::DataConversion, e))?
+        .format(&Rfc3339)
+        .map_err(|e| Error::new(ErrorKind::DataConversion, e))
+}
+
+/// The account name and key used to sign a SAS that cannot use a user
+/// delegation key
+pub(crate) async fn shared_key(rest_client: &RestClient, kind: &str) -> Result<(String, Secret)> {
+    rest_client.shared_key().await.ok_or_else(|| {
+        Error::with_message(ErrorKind::Credential, || {
+            format!("{kind} SAS must be signed with a shared key, use --access-key")
+        })
+    })
+}
+
+pub(crate) fn sign(string_to_sign: &str, key: &Secret) -> Result<String> {
+    hmac_sha256(string_to_sign, key)
+}
+
+/// Parse a set of single letter flags, such as the `ss` and `srt` fields,
+/// returning them in the canonical order.
+pub(crate) fn parse_flags(s: &str, allowed: &str, name: &str) -> Result<String> {
+    if let Some(invalid) = s.chars().find(|c| !allowed.contains(*c)) {
+        return Err(Error::with_message(ErrorKind::DataConversion, || {
+            format!("invalid {name} `{invalid}`, expected any of `{allowed}`")
+        }));
+    }
+    if s.is_empty() {
+        return Err(Error::with_message(ErrorKind::DataConversion, || {
+            format!("at least one of `{allowed}` is required for {name}")
+        }));
+    }
+    Ok(allowed.chars().filter(|c| s.contains(*c)).collect())
+}
+
+/// Services accessible with an account SAS, as any combination of `b` (blob),
+/// `q` (queue), `t` (table), and `f` (file)
+#[derive(Debug, Clone)]
+pub struct AccountSasServices(String);
+
+impl AccountSasServices {
+    pub(crate) fn contains(&self, service: char) -> bool {
+        self.0.contains(service)
+    }
+}
+
+impl FromStr for AccountSasServices {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        parse_flags(s, "bqtf", "service").map(Self)
+    }
+}
+
+impl fmt::Display for AccountSasServices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Resource types accessible with an account SAS, as any combination of `s`
+/// (service), `c` (container), and `o` (object)
+#[derive(Debug, Clone)]
+pub struct AccountSasResourceTypes(String);
+
+impl FromStr for AccountSasResourceTypes {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        parse_flags(s, "sco", "resource type").map(Self)
+    }
+}
+
+impl fmt::Display for AccountSasResourceTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Permissions that can be granted by a blob, container or directory SAS, in
+/// the order the service requires
+pub(crate) const BLOB_PERMISSIONS: &str = "racwdxyltmeop";
+
+/// Permissions for a blob, container or directory SAS
+#[derive(Debug, Args)]
+#[group(required = true, multiple = true)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct SasPermissions {
+    /// Permissions as a compact string such as `racwdl`, combined with any specified individually
+    #[clap(long, value_name = "PERMISSIONS")]
+    permission_string: Option<String>,
+    #[clap(long)]
+    read: bool,
+    #[clap(long)]
+    add: bool,
+    #[clap(long)]
+    create: bool,
+    #[clap(long)]
+    write: bool,
+    #[clap(long)]
+    delete: bool,
+    #[clap(long)]
+    delete_version: bool,
+    #[clap(long)]
+    list: bool,
+    #[clap(long)]
+    tags: bool,
+    #[clap(long, name = "move")]
+    move_: bool,
+    #[clap(long)]
+    execute: bool,
+    #[clap(long)]
+    ownership: bool,
+    #[clap(long)]
+    permissions: bool,
+    #[clap(long)]
+    permanent_delete: bool,
+}
+
+impl TryFrom<SasPermissions> for BlobSasPermissions {
+    type Error = Error;
+
+    fn try_from(permissions: SasPermissions) -> Result<Self> {
+        let flags = permissions
+            .permission_string
+            .as_deref()
+            .map(|s| parse_flags(s, BLOB_PERMISSIONS, "permission"))
+            .transpose()?
+            .unwrap_or_default();
+        let has = |flag| flags.contains(flag);
+        Ok(Self {
+            read: permissions.read || has('r'),
+            add: permissions.add || has('a'),
+            create: permissions.create || has('c'),
+            write: permissions.write || has('w'),
+            delete: permissions.delete || has('d'),
+            delete_version: permissions.delete_version || has('x'),
+            permanent_delete: permissions.permanent_delete || has('y'),
+            list: permissions.list || has('l'),
+            tags: permissions.tags || has('t'),
+            move_: permissions.move_ || has('m'),
+            execute: permissions.execute || has('e'),
+            ownership: permissions.ownership || has('o'),
+            permissions: permissions.permissions || has('p'),
+        })
+    }
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = true)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct AccountSasPermissions {
+    #[clap(long)]
+    read: bool,
+    #[clap(long)]
+    write: bool,
+    #[clap(long)]
+    delete: bool,
+    #[clap(long)]
+    delete_version: bool,
+    #[clap(long)]
+    permanent_delete: bool,
+    #[clap(long)]
+    list: bool,
+    #[clap(long)]
+    add: bool,
+    #[clap(long)]
+    create: bool,
+    #[clap(long)]
+    update: bool,
+    #[clap(long)]
+    process: bool,
+    #[clap(long)]
+    tags: bool,
+    #[clap(long)]
+    filter_tags: bool,
+    #[clap(long)]
+    set_immutability_policy: bool,
+}
+
+impl fmt::Display for AccountSasPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The service requires the permissions in this order
+        for (enabled, flag) in [
+            (self.read, 'r'),
+            (self.write, 'w'),
+            (self.delete, 'd'),
+            (self.delete_version, 'x'),
+            (self.permanent_delete, 'y'),
+            (self.list, 'l'),
+            (self.add, 'a'),
+            (self.create, 'c'),
+            (self.update, 'u'),
+            (self.process, 'p'),
+            (self.tags, 't'),
+            (self.filter_tags, 'f'),
+            (self.set_immutability_policy, 'i'),
+        ] {
+            if enabled {
+                write!(f, "{flag}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Create an account SAS token
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/create-account-sas>
+pub(crate) fn account_sas(
+    account: &str,
+    key: &Secret,
+    services: &AccountSasServices,
+    resource_types: &AccountSasResourceTypes,
+    permissions: &AccountSasPermissions,
+    validity: &SasValidity,
+    encryption_scope: Option<&str>,
+) -> Result<String> {
+    let string_to_sign = format!(
+        "{account}\n{permissions}\n{services}\n{resource_types}\n{}\n{}\n{}\n{}\n{SAS_VERSION}\n{}\n",
+        validity.start()?,
+        validity.expiry()?,
+        validity.ip(),
+        validity.protocol(),
+        encryption_scope.unwrap_or_default(),
+    );
+
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    form.append_pair("sv", SAS_VERSION)
+        .append_pair("ss", &services.to_string())
+        .append_pair("srt", &resource_types.to_string())
+        .append_pair("sp", &permissions.to_string())
+        .append_pair("se", &validity.expiry()?);
+    validity.append_to(&mut form)?;
+    if let Some(encryption_scope) = encryption_scope {
+        form.append_pair("ses", encryption_scope);
+    }
+    form.append_pair("sig", &sign(&string_to_sign, key)?);
+    Ok(form.finish())
+}
+
+/// Permissions for a queue SAS
+#[derive(Debug, Args)]
+#[group(required = true, multiple = true)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct QueueSasPermissions {
+    /// Read metadata and properties, and peek messages
+    #[clap(long)]
+    read: bool,
+    /// Add messages
+    #[clap(long)]
+    add: bool,
+    /// Update messages
+    #[clap(long)]
+    update: bool,
+    /// Get and delete messages
+    #[clap(long)]
+    process: bool,
+}
+
+impl fmt::Display for QueueSasPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (enabled, flag) in [
+            (self.read, 'r'),
+            (self.add, 'a'),
+            (self.update, 'u'),
+            (self.process, 'p'),
+        ] {
+            if enabled {
+                write!(f, "{flag}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Permissions for a table SAS
+#[derive(Debug, Args)]
+#[group(required = true, multiple = true)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct TableSasPermissions {
+    /// Query entities
+    #[clap(long)]
+    read: bool,
+    /// Insert entities
+    #[clap(long)]
+    add: bool,
+    /// Update entities
+    #[clap(long)]
+    update: bool,
+    /// Delete entities
+    #[clap(long)]
+    delete: bool,
+}
+
+impl fmt::Display for TableSasPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (enabled, flag) in [
+            (self.read, 'r'),
+            (self.add, 'a'),
+            (self.update, 'u'),
+            (self.delete, 'd'),
+        ] {
+            if enabled {
+                write!(f, "{flag}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Range of entities accessible with a table SAS
+#[derive(Debug, Args)]
+#[allow(clippy::struct_field_names)]
+pub(crate) struct TableKeyRange {
+    /// Lowest partition key accessible with the SAS
+    #[clap(long)]
+    start_partition_key: Option<String>,
+    /// Lowest row key accessible with the SAS (requires `--start-partition-key`)
+    #[clap(long, requires = "start_partition_key")]
+    start_row_key: Option<String>,
+    /// Highest partition key accessible with the SAS
+    #[clap(long)]
+    end_partition_key: Option<String>,
+    /// Highest row key accessible with the SAS (requires `--end-partition-key`)
+    #[clap(long, requires = "end_partition_key")]
+    end_row_key: Option<String>,
+}
+
+impl TableKeyRange {
+    fn keys(&self) -> [(&'static str, Option<&str>); 4] {
+        [
+            ("spk", self.start_partition_key.as_deref()),
+            ("srk", self.start_row_key.as_deref()),
+            ("epk", self.end_partition_key.as_deref()),
+            ("erk", self.end_row_key.as_deref()),
+        ]
+    }
+}
+
+/// Create a service SAS token for a queue
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/create-service-sas>
+pub(crate) fn queue_sas(
+    account: &str,
+    queue_name: &str,
+    key: &Secret,
+    permissions: &QueueSasPermissions,
+    validity: &SasValidity,
+    identifier: Option<&str>,
+) -> Result<String> {
+    let string_to_sign = format!(
+        "{permissions}\n{}\n{}\n/queue/{account}/{queue_name}\n{}\n{}\n{}\n{SAS_VERSION}",
+        validity.start()?,
+        validity.expiry()?,
+        identifier.unwrap_or_default(),
+        validity.ip(),
+        validity.protocol(),
+    );
+
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    form.append_pair("sv", SAS_VERSION)
+        .append_pair("sp", &permissions.to_string())
+        .append_pair("se", &validity.expiry()?);
+    validity.append_to(&mut form)?;
+    if let Some(identifier) = identifier {
+        form.append_pair("si", identifier);
+    }
+    form.append_pair("sig", &sign(&string_to_sign, key)?);
+    Ok(form.finish())
+}
+
+/// Create a service SAS token for a table, optionally limited to a range of
+/// partition and row keys
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/create-service-sas>
+pub(crate) fn table_sas(
+    account: &str,
+    table_name: &str,
+    key: &Secret,
+    permissions: &TableSasPermissions,
+    range: &TableKeyRange,
+    validity: &SasValidity,
+    identifier: Option<&str>,
+) -> Result<String> {
+    let mut string_to_sign = format!(
+        "{permissions}\n{}\n{}\n/table/{account}/{}\n{}\n{}\n{}\n{SAS_VERSION}",
+        validity.start()?,
+        validity.expiry()?,
+        table_name.to_lowercase(),
+        identifier.unwrap_or_default(),
+        validity.ip(),
+        validity.protocol(),
+    );
+    for (_, value) in range.keys() {
+        string_to_sign.push('\n');
+        string_to_sign.push_str(value.unwrap_or_default());
+    }
+
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    form.append_pair("sv", SAS_VERSION)
+        .append_pair("tn", table_name)
+        .append_pair("sp", &permissions.to_string())
+        .append_pair("se", &validity.expiry()?);
+    validity.append_to(&mut form)?;
+    if let Some(identifier) = identifier {
+        form.append_pair("si", identifier);
+    }
+    for (name, value) in range.keys() {
+        if let Some(value) = value {
+            form.append_pair(name, value);
+        }
+    }
+    form.append_pair("sig", &sign(&string_to_sign, key)?);
+    Ok(form.finish())
+}
+
+/// Create a service SAS token for a datalake directory, signed with either the
+/// account key or a user delegation key
+///
+/// See <https://learn.microsoft.com/rest/api/storageservices/create-service-sas>
+/// and <https://learn.microsoft.com/rest/api/storageservices/create-user-delegation-sas>
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn directory_sas(
+    account: &str,
+    file_system: &str,
+    directory: &str,
+    depth: usize,
+    key: &SasKey,
+    permissions: &BlobSasPermissions,
+    validity: &SasValidity,
+    identifier: Option<&str>,
+) -> Result<String> {
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    form.append_pair("sv", SAS_VERSION)
+        .append_pair("sr", "d")
+        .append_pair("sdd", &depth.to_string())
+        .append_pair("sp", &permissions.to_string())
+        .append_pair("se", &validity.expiry()?);
+    validity.append_to(&mut form)?;
+
+    // The fields between the canonicalized resource and the IP range depend
+    // on the kind of key
+    let (key_fields, secret) = match key {
+        SasKey::Key(secret) => {
+            if let Some(identifier) = identifier {
+                form.append_pair("si", identifier);
+            }
+            (identifier.unwrap_or_default().to_string(), secret)
+        }
+        SasKey::UserDelegationKey(key) => {
+            if identifier.is_some() {
+                return Err(Error::message(
+                    ErrorKind::Credential,
+                    "stored access policies require a SAS signed with a shared key, use --access-key",
+                ));
+            }
+            let fields = [
+                ("skoid", key.signed_oid.to_string()),
+                ("sktid", key.signed_tid.to_string()),
+                ("skt", format_time(key.signed_start)?),
+                ("ske", format_time(key.signed_expiry)?),
+                ("sks", key.signed_service.clone()),
+                ("skv", key.signed_version.clone()),
+            ];
+            for (name, value) in &fields {
+                form.append_pair(name, value);
+            }
+            let mut key_fields = fields
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            // authorized object id, unauthorized object id, and correlation id
+            key_fields.extend([String::new(), String::new(), String::new()]);
+            (key_fields.join("\n"), &key.value)
+        }
+    };
+
+    // The trailing empty fields are the snapshot time, encryption scope, and
+    // response header overrides, none of which are used
+    let string_to_sign = format!(
+        "{permissions}\n{}\n{}\n/blob/{account}/{file_system}/{}\n{key_fields}\n{}\n{}\n{SAS_VERSION}\nd\n\n\n\n\n\n\n",
+        validity.start()?,
+        validity.expiry()?,
+        directory.trim_matches('/'),
+        validity.ip(),
+        validity.protocol(),
+    );
+    form.append_pair("sig", &sign(&string_to_sign, secret)?);
+    Ok(form.finish())
+}
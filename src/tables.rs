@@ -1,9 +1,23 @@
-use crate::{args, utils::output};
+use crate::{
+    acl::{get_acl, update_acl, Acl, PolicyChange},
+    paging::Paging,
+    rest::RestClient,
+    sas::{shared_key, table_sas, SasOptions, TableKeyRange, TableSasPermissions},
+    utils::output,
+};
+use azure_core::{
+    error::{Error, ErrorKind},
+    Url,
+};
 use azure_data_tables::{clients::TableServiceClient, Filter, IfMatchCondition, Select};
+use azure_storage::clients::ServiceType;
 use clap::Subcommand;
 use serde_json::Value;
 use std::{collections::HashMap, path::PathBuf};
 
+/// Permissions that can be granted by a table's stored access policy
+const TABLE_PERMISSIONS: &str = "raud";
+
 #[derive(Subcommand)]
 pub enum TableSubCommands {
     /// List available tables
@@ -29,7 +43,9 @@ pub enum TableSubCommands {
     },
     /// Query a table
     ///
-    /// The output of this command is serialized as JSON
+    /// The output of this command is serialized as JSON.  Continuation markers
+    /// are a JSON array of the next partition key and the next row key, which
+    /// is `null` when there is none.
     Query {
         /// table name
         table_name: String,
@@ -37,8 +53,11 @@ pub enum TableSubCommands {
         filter: Option<Filter>,
         #[clap(long)]
         select: Option<Select>,
-        #[clap(long)]
+        /// max entities to return per page
+        #[clap(long, visible_alias = "page-size")]
         top: Option<u32>,
+        #[clap(flatten)]
+        paging: Paging,
     },
     /// Get a specific row in the table
     ///
@@ -108,11 +127,50 @@ pub enum TableSubCommands {
         #[clap(long)]
         if_match_condition: Option<String>,
     },
+    /// Get the stored access policies of a table
+    GetAcl {
+        /// table name
+        table_name: String,
+    },
+    /// Change the stored access policies of a table
+    SetAcl {
+        /// table name
+        table_name: String,
+        #[clap(subcommand)]
+        change: PolicyChange,
+    },
+    /// Generate a SAS URL for a table, signed with the account access key
+    ///
+    /// Tables do not support user delegation SAS, so this requires `--access-key`
+    GenerateSas {
+        /// table name
+        table_name: String,
+        #[clap(flatten)]
+        options: SasOptions,
+        /// Stored access policy on the table
+        #[clap(long)]
+        identifier: Option<String>,
+        #[clap(flatten)]
+        range: TableKeyRange,
+        #[clap(flatten)]
+        permissions: TableSasPermissions,
+    },
+}
+
+/// The SDK's table clients do not expose their URLs
+fn table_url(rest_client: &RestClient, table_name: &str) -> azure_core::Result<Url> {
+    let mut url = rest_client.service_url("table")?;
+    url.path_segments_mut()
+        .map_err(|()| Error::message(ErrorKind::DataConversion, "invalid table endpoint"))?
+        .pop_if_empty()
+        .push(table_name);
+    Ok(url)
 }
 
 #[allow(clippy::too_many_lines)]
 pub async fn table_commands(
     service_client: &TableServiceClient,
+    rest_client: &RestClient,
     subcommand: TableSubCommands,
 ) -> azure_core::Result<()> {
     match subcommand {
@@ -136,10 +194,19 @@ pub async fn table_commands(
             filter,
             select,
             top,
+            paging,
         } => {
             let mut builder = service_client.table_client(&table_name).query();
             args!(builder, filter, select, top);
-            output_stream_entries!(builder.into_stream::<Value>(), entities);
+            if let Some((partition_key, row_key)) = paging.table_keys()? {
+                builder = builder.initial_partition_key(partition_key);
+                if let Some(row_key) = row_key {
+                    builder = builder.initial_row_key(row_key);
+                }
+            }
+            paging
+                .output_stream(builder.into_stream::<Value>(), |page| Ok(page.entities))
+                .await?;
         }
         TableSubCommands::Get {
             table_name,
@@ -240,6 +307,43 @@ pub async fn table_commands(
                 .merge(entity, if_match_condition)?
                 .await?;
         }
+        TableSubCommands::GetAcl { table_name } => {
+            let url = table_url(rest_client, &table_name)?;
+            let (_, policies) = get_acl(rest_client, ServiceType::Table, url).await?;
+            output(&Acl::new(None, policies))?;
+        }
+        TableSubCommands::SetAcl { table_name, change } => {
+            let url = table_url(rest_client, &table_name)?;
+            update_acl(
+                rest_client,
+                ServiceType::Table,
+                url,
+                change,
+                TABLE_PERMISSIONS,
+            )
+            .await?;
+        }
+        TableSubCommands::GenerateSas {
+            table_name,
+            options,
+            identifier,
+            range,
+            permissions,
+        } => {
+            let (account, key) = shared_key(rest_client, "table").await?;
+            let token = table_sas(
+                &account,
+                &table_name,
+                &key,
+                &permissions,
+                &range,
+                &options.validity()?,
+                identifier.as_deref(),
+            )?;
+            let mut url = table_url(rest_client, &table_name)?;
+            url.set_query(Some(&token));
+            println!("{url}");
+        }
     }
     Ok(())
 }
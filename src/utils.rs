@@ -3,14 +3,27 @@ use azure_core::{
     date::parse_rfc3339,
     error::{Error, ErrorKind},
     request_options::Metadata,
+    StatusCode,
 };
 use azure_storage_blobs::prelude::Tags;
 use azure_storage_datalake::Properties;
 use clap::ValueEnum;
 use duration_string::DurationString;
+use md5::{Digest, Md5};
 use serde::Serialize;
-use std::{error::Error as StdError, io::stdout, ops::Add, str::FromStr, time::Duration};
+use std::{
+    error::Error as StdError,
+    io::{stderr, stdin, stdout, Write},
+    ops::Add,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
 use time::OffsetDateTime;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt},
+};
 
 /// Parse a single key-value pair of `X=Y` into a typed tuple of `(X, Y)`.
 ///
@@ -58,6 +71,39 @@ pub(crate) fn round_up(x: u64, y: u64) -> u64 {
     (x / y + u64::from(!x.is_multiple_of(y))) * y
 }
 
+/// Read up to `block_size` bytes, only returning a short block at the end of the input
+pub(crate) async fn read_block<R>(handle: &mut R, block_size: usize) -> azure_core::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut block = Vec::with_capacity(block_size);
+    while block.len() < block_size {
+        let mut take = (&mut *handle).take((block_size - block.len()) as u64);
+        if take.read_buf(&mut block).await? == 0 {
+            break;
+        }
+    }
+    Ok(block)
+}
+
+/// Size of the reads used to compute the MD5 of a local file
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Compute the MD5 of a local file, for comparison with a blob's `Content-MD5`
+pub(crate) async fn file_md5(path: &Path) -> azure_core::Result<[u8; 16]> {
+    let mut handle = File::open(path).await?;
+    let mut hasher = Md5::new();
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    loop {
+        let read = handle.read(&mut buffer).await?;
+        let Some(data) = buffer.get(..read).filter(|data| !data.is_empty()) else {
+            break;
+        };
+        hasher.update(data);
+    }
+    Ok(hasher.finalize().into())
+}
+
 pub(crate) fn parse_time(s: &str, format: TimeFormat) -> azure_core::Result<OffsetDateTime> {
     match format {
         TimeFormat::Rfc3339 => parse_rfc3339(s),
@@ -79,6 +125,57 @@ pub(crate) fn parse_duration(s: &str) -> azure_core::Result<Duration> {
     Ok(duration)
 }
 
+/// The HTTP status of a failed request, if the error came from the service
+pub(crate) fn http_status(error: &Error) -> Option<StatusCode> {
+    error
+        .as_http_error()
+        .map(azure_core::error::HttpError::status)
+}
+
+/// Ask the user to confirm an action on STDERR, treating anything other than
+/// `y` or `yes` as a refusal.
+pub(crate) fn confirm(prompt: &str) -> azure_core::Result<bool> {
+    eprint!("{prompt} [y/N] ");
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// The name of an SDK enum, such as an access tier, which is only exposed
+/// through its serialized form
+pub(crate) fn serialized_name<T>(value: &T) -> String
+where
+    T: Serialize,
+{
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+/// Format a size in bytes using binary units, such as `1.5 GiB`
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
+
 pub(crate) fn output<T>(value: &T) -> azure_core::Result<()>
 where
     T: ?Sized + Serialize,